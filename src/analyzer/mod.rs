@@ -0,0 +1,278 @@
+use crate::ast::{AggFunc, ColumnSpans, Query, Value};
+use crate::engine::{coerce_comparison, source_schema};
+use crate::errors::{did_you_mean, QueryError};
+use crate::plan::{lower, LogicalPlan, PlanExpr};
+use polars::prelude::{col, DataType, Field, Schema};
+
+/// Checks a query against the data before running it: reads each source's
+/// schema, follows it through every stage, resolves every column reference
+/// (including `partition by` columns of the result) and type-checks
+/// comparisons and aggregate arguments. `spans` (from `Parser::column_spans`)
+/// point errors at the query text.
+pub fn analyze(query: &Query, spans: &ColumnSpans) -> Result<(), QueryError> {
+    let plan = lower(query.clone())?;
+    let analyzer = Analyzer { spans };
+    let scope = analyzer.scope(&plan)?;
+    if let Some(write) = &query.write {
+        for column in &write.partition_by {
+            analyzer.resolve(&scope, column)?;
+        }
+    }
+    Ok(())
+}
+
+struct Analyzer<'a> {
+    /// Where the query text references each column, for error spans.
+    spans: &'a ColumnSpans,
+}
+
+/// Columns available at a point in the plan.
+struct Scope {
+    columns: Schema,
+    /// After a `pivot` the output columns depend on the data, so unknown names are let through.
+    open: bool,
+}
+
+/// How a column or literal takes part in comparisons.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Numeric,
+    Text,
+    Bool,
+    Temporal,
+    /// Type not known until run time (e.g. an all-null column or a pivot output).
+    Any,
+    Other,
+}
+
+impl Analyzer<'_> {
+    fn scope(&self, plan: &LogicalPlan) -> Result<Scope, QueryError> {
+        use LogicalPlan::*;
+        Ok(match plan {
+            Scan { source } => {
+                let columns = source_schema(source).map_err(|e| QueryError::Unexpected(e.to_string()))?;
+                Scope { columns, open: false }
+            }
+            Filter { input, predicate } => {
+                let scope = self.scope(input)?;
+                self.check_expr(&scope, predicate)?;
+                scope
+            }
+            Project { input, columns } => {
+                let scope = self.scope(input)?;
+                let fields = columns
+                    .iter()
+                    .map(|name| Ok(Field::new(name, self.resolve(&scope, name)?)))
+                    .collect::<Result<Vec<_>, QueryError>>()?;
+                Scope { columns: fields.into_iter().collect(), open: false }
+            }
+            Map { input, name, expr } => {
+                let mut scope = self.scope(input)?;
+                self.check_expr(&scope, expr)?;
+                scope.columns.with_column(name.as_str().into(), DataType::Boolean);
+                scope
+            }
+            Aggregate { input, keys, aggregations } => {
+                let scope = self.scope(input)?;
+                let mut columns = Schema::new();
+                for key in keys {
+                    columns.with_column(key.as_str().into(), self.resolve(&scope, key)?);
+                }
+                for aggregation in aggregations {
+                    let dtype = self.resolve(&scope, &aggregation.column)?;
+                    self.check_aggregate(aggregation.func, &aggregation.column, &dtype)?;
+                    columns.with_column(aggregation.alias.as_str().into(), DataType::Unknown);
+                }
+                Scope { columns, open: false }
+            }
+            Sort { input, sort } => {
+                let scope = self.scope(input)?;
+                self.resolve(&scope, &sort.column)?;
+                scope
+            }
+            Limit { input, .. } | Distinct { input } | Sample { input, .. } => self.scope(input)?,
+            Join { left, right, on, .. } => {
+                let mut scope = self.scope(left)?;
+                let other = self.scope(right)?;
+                for key in on {
+                    self.resolve(&scope, key)?;
+                    self.resolve(&other, key)?;
+                }
+                for (name, dtype) in other.columns.iter() {
+                    if on.iter().any(|key| key == name.as_str()) {
+                        continue;
+                    }
+                    let name = if scope.columns.contains(name) {
+                        format!("{}_right", name)
+                    } else {
+                        name.to_string()
+                    };
+                    scope.columns.with_column(name.into(), dtype.clone());
+                }
+                scope.open |= other.open;
+                scope
+            }
+            Cluster { input, cluster } => {
+                let mut scope = self.scope(input)?;
+                for column in &cluster.columns {
+                    let dtype = self.resolve(&scope, column)?;
+                    if !matches!(kind(&dtype), Kind::Numeric | Kind::Any) {
+                        return Err(self.error(
+                            format!("'cluster by' needs numeric columns, but '{}' is {}", column, dtype),
+                            column,
+                        ));
+                    }
+                }
+                scope.columns.with_column(cluster.alias.as_str().into(), DataType::UInt32);
+                if cluster.centroids {
+                    for column in &cluster.columns {
+                        scope.columns.with_column(format!("{}_{}", cluster.alias, column).into(), DataType::Float64);
+                    }
+                }
+                scope
+            }
+            Pivot { input, pivot } => {
+                let scope = self.scope(input)?;
+                self.resolve(&scope, &pivot.columns)?;
+                let dtype = self.resolve(&scope, &pivot.values)?;
+                self.check_aggregate(pivot.agg, &pivot.values, &dtype)?;
+                let mut columns = Schema::new();
+                for name in &pivot.index {
+                    columns.with_column(name.as_str().into(), self.resolve(&scope, name)?);
+                }
+                Scope { columns, open: true }
+            }
+            Unpivot { input, unpivot } => {
+                let scope = self.scope(input)?;
+                for name in &unpivot.columns {
+                    self.resolve(&scope, name)?;
+                }
+                let mut columns: Schema = scope
+                    .columns
+                    .iter_fields()
+                    .filter(|field| !unpivot.columns.iter().any(|c| c == field.name().as_str()))
+                    .collect();
+                columns.with_column(unpivot.variable.as_str().into(), DataType::String);
+                columns.with_column(unpivot.value.as_str().into(), DataType::Unknown);
+                Scope { columns, open: scope.open }
+            }
+        })
+    }
+
+    /// Type of `name` in `scope`, or an error pointing at where it was written.
+    fn resolve(&self, scope: &Scope, name: &str) -> Result<DataType, QueryError> {
+        match scope.columns.get(name) {
+            Some(dtype) => Ok(dtype.clone()),
+            None if scope.open => Ok(DataType::Unknown),
+            None => {
+                let available: Vec<&str> = scope.columns.iter_names().map(|n| n.as_str()).collect();
+                let message = match did_you_mean(name, available.iter().copied()) {
+                    Some(closest) => format!("Unknown column '{}'; did you mean '{}'?", name, closest),
+                    None => format!("Unknown column '{}' (available: {})", name, available.join(", ")),
+                };
+                Err(self.error(message, name))
+            }
+        }
+    }
+
+    fn check_expr(&self, scope: &Scope, expr: &PlanExpr) -> Result<(), QueryError> {
+        match expr {
+            PlanExpr::Column(name) => self.resolve(scope, name).map(|_| ()),
+            PlanExpr::Literal(_) => Ok(()),
+            PlanExpr::And(lhs, rhs) | PlanExpr::Or(lhs, rhs) => {
+                self.check_expr(scope, lhs)?;
+                self.check_expr(scope, rhs)
+            }
+            PlanExpr::Compare(_, lhs, rhs) => {
+                if let (PlanExpr::Column(name), PlanExpr::Literal(value))
+                | (PlanExpr::Literal(value), PlanExpr::Column(name)) = (&**lhs, &**rhs)
+                {
+                    let dtype = self.resolve(scope, name)?;
+                    return coerce_comparison(col(name), &dtype, value).map(|_| ()).map_err(|reason| {
+                        let literal = PlanExpr::Literal(value.clone());
+                        self.error(
+                            format!(
+                                "Cannot compare column '{}' ({}) with {} {}: {}",
+                                name,
+                                dtype,
+                                value.type_name(),
+                                literal,
+                                reason
+                            ),
+                            name,
+                        )
+                    });
+                }
+                let (lhs_kind, lhs_desc) = self.operand(scope, lhs)?;
+                let (rhs_kind, rhs_desc) = self.operand(scope, rhs)?;
+                if comparable(lhs_kind, rhs_kind) {
+                    return Ok(());
+                }
+                let column = [&**lhs, &**rhs].into_iter().find_map(|side| match side {
+                    PlanExpr::Column(name) => Some(name.as_str()),
+                    _ => None,
+                });
+                Err(self.error(
+                    format!("Cannot compare {} with {} in '{}'", lhs_desc, rhs_desc, expr),
+                    column.unwrap_or_default(),
+                ))
+            }
+        }
+    }
+
+    /// Kind and a short description of one side of a comparison.
+    fn operand(&self, scope: &Scope, expr: &PlanExpr) -> Result<(Kind, String), QueryError> {
+        Ok(match expr {
+            PlanExpr::Column(name) => {
+                let dtype = self.resolve(scope, name)?;
+                (kind(&dtype), format!("column '{}' ({})", name, dtype))
+            }
+            PlanExpr::Literal(value) => (value_kind(value), format!("{} {}", value.type_name(), expr)),
+            other => {
+                self.check_expr(scope, other)?;
+                (Kind::Bool, format!("'{}'", other))
+            }
+        })
+    }
+
+    fn check_aggregate(&self, func: AggFunc, column: &str, dtype: &DataType) -> Result<(), QueryError> {
+        let allowed = match func {
+            AggFunc::Sum | AggFunc::Mean | AggFunc::Median => {
+                matches!(kind(dtype), Kind::Numeric | Kind::Bool | Kind::Any)
+            }
+            AggFunc::Min | AggFunc::Max => kind(dtype) != Kind::Other,
+            AggFunc::Count | AggFunc::First | AggFunc::Last => true,
+        };
+        if allowed {
+            return Ok(());
+        }
+        Err(self.error(format!("{}() can't aggregate column '{}' ({})", func.name(), column, dtype), column))
+    }
+
+    fn error(&self, message: String, column: &str) -> QueryError {
+        QueryError::Semantic { message, span: self.spans.span_of(column) }
+    }
+}
+
+fn kind(dtype: &DataType) -> Kind {
+    match dtype {
+        dtype if dtype.is_numeric() => Kind::Numeric,
+        DataType::String => Kind::Text,
+        DataType::Boolean => Kind::Bool,
+        DataType::Date | DataType::Datetime(_, _) | DataType::Time | DataType::Duration(_) => Kind::Temporal,
+        DataType::Null | DataType::Unknown => Kind::Any,
+        _ => Kind::Other,
+    }
+}
+
+fn value_kind(value: &Value) -> Kind {
+    match value {
+        Value::Number(_) | Value::Float(_) => Kind::Numeric,
+        Value::String(_) => Kind::Text,
+        Value::Bool(_) => Kind::Bool,
+    }
+}
+
+fn comparable(a: Kind, b: Kind) -> bool {
+    a == Kind::Any || b == Kind::Any || (a == b && a != Kind::Other)
+}
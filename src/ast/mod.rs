@@ -1,12 +1,341 @@
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct Query {
-    pub source: String,
+    pub source: Source,
     pub filter: Option<Expr>,
     pub show: Vec<String>,
     pub sort: Option<Sort>,
     pub cap: Option<usize>,
     pub map: Option<(String, Expr)>,
     pub unique: bool,
+    pub sample: Option<Sample>,
+    pub cluster: Option<Cluster>,
+    pub pivot: Option<Pivot>,
+    pub unpivot: Option<Unpivot>,
+    /// `write to "out/" ...`: where the result is saved when the command line doesn't say.
+    pub write: Option<WriteTo>,
+    /// `explain source ...`: describe how the query would run instead of running it.
+    pub explain: bool,
+}
+
+/// Where each referenced column first appears in the query text. Kept beside
+/// the `Query` (see `Parser::column_spans`) so two queries compare equal
+/// however they were written.
+#[derive(Debug, Clone, Default)]
+pub struct ColumnSpans(Vec<(String, Span)>);
+
+impl ColumnSpans {
+    pub fn span_of(&self, column: &str) -> Option<Span> {
+        self.0.iter().find(|(name, _)| name == column).map(|(_, span)| *span)
+    }
+
+    /// Records a reference unless the column was seen before.
+    pub fn record(&mut self, column: &str, span: Span) {
+        if self.span_of(column).is_none() {
+            self.0.push((column.to_string(), span));
+        }
+    }
+}
+
+/// `write to "out/" format parquet mode overwrite partition by year, city`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct WriteTo {
+    pub path: String,
+    /// Explicit `format parquet`; otherwise the format follows the path's extension.
+    pub format: Option<FileFormat>,
+    pub mode: WriteMode,
+    /// Writes one file per group under Hive-style directories (`year=2024/city=Recife/`).
+    pub partition_by: Vec<String>,
+}
+
+/// What happens when the output file already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum WriteMode {
+    #[default]
+    Overwrite,
+    /// Adds rows to the end of the file; CSV and NDJSON only.
+    Append,
+    ErrorIfExists,
+}
+
+impl WriteMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "overwrite" => WriteMode::Overwrite,
+            "append" => WriteMode::Append,
+            "error_if_exists" => WriteMode::ErrorIfExists,
+            _ => return None,
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            WriteMode::Overwrite => "overwrite",
+            WriteMode::Append => "append",
+            WriteMode::ErrorIfExists => "error_if_exists",
+        }
+    }
+}
+
+/// Byte range of a token in the query text.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Source path that reads from standard input, written `source "-"` or `source stdin`.
+pub const STDIN_PATH: &str = "-";
+
+/// Input files, e.g. `source "jan.csv", "feb.csv"`, `source "sales/*.csv" with source_file`
+/// or `source "events" as ndjson schema (id string)`.
+/// Multiple files are concatenated with columns aligned by name.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Source {
+    pub paths: Vec<String>,
+    /// Adds a `_source_file` column holding the path each row came from.
+    pub tag_file: bool,
+    /// Explicit `as parquet`; otherwise the format follows each file's extension.
+    pub format: Option<FileFormat>,
+    pub options: CsvOptions,
+    pub schema: Option<SourceSchema>,
+}
+
+/// Declared column types, e.g. `schema (id string, age int, joined date)`.
+/// Undeclared columns are still inferred unless the schema is `exact`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SourceSchema {
+    pub columns: Vec<ColumnDef>,
+    /// `schema exact (...)`: the file must contain exactly the declared columns.
+    pub exact: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnDef {
+    pub name: String,
+    pub ty: ColumnType,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnType {
+    String,
+    Int,
+    Float,
+    Bool,
+    Date,
+    Datetime,
+}
+
+impl ColumnType {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "string" | "str" | "text" => ColumnType::String,
+            "int" | "integer" | "i64" => ColumnType::Int,
+            "float" | "double" | "f64" => ColumnType::Float,
+            "bool" | "boolean" => ColumnType::Bool,
+            "date" => ColumnType::Date,
+            "datetime" | "timestamp" => ColumnType::Datetime,
+            _ => return None,
+        })
+    }
+
+    /// The canonical name, as accepted by `from_name`.
+    pub fn name(self) -> &'static str {
+        match self {
+            ColumnType::String => "string",
+            ColumnType::Int => "int",
+            ColumnType::Float => "float",
+            ColumnType::Bool => "bool",
+            ColumnType::Date => "date",
+            ColumnType::Datetime => "datetime",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileFormat {
+    Csv,
+    Parquet,
+    /// Newline-delimited JSON, one object per line.
+    Ndjson,
+    /// A single JSON array of objects.
+    Json,
+    /// Arrow IPC / Feather.
+    Ipc,
+}
+
+impl FileFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "csv" => FileFormat::Csv,
+            "parquet" | "pq" => FileFormat::Parquet,
+            "ndjson" | "jsonl" => FileFormat::Ndjson,
+            "json" => FileFormat::Json,
+            "ipc" | "arrow" | "feather" => FileFormat::Ipc,
+            _ => return None,
+        })
+    }
+
+    /// The canonical name, as accepted by `from_name`.
+    pub fn name(self) -> &'static str {
+        match self {
+            FileFormat::Csv => "csv",
+            FileFormat::Parquet => "parquet",
+            FileFormat::Ndjson => "ndjson",
+            FileFormat::Json => "json",
+            FileFormat::Ipc => "ipc",
+        }
+    }
+
+    /// Guesses the format from a path's extension, falling back to CSV.
+    pub fn from_path(path: &str) -> Self {
+        std::path::Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(FileFormat::from_name)
+            .unwrap_or(FileFormat::Csv)
+    }
+}
+
+/// CSV dialect set through `with (delimiter ";", header false, null "NA", ...)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvOptions {
+    pub delimiter: u8,
+    pub quote: Option<u8>,
+    pub header: bool,
+    pub null_values: Vec<String>,
+    pub encoding: Encoding,
+    pub skip_rows: usize,
+    pub comment: Option<String>,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            delimiter: b',',
+            quote: Some(b'"'),
+            header: true,
+            null_values: Vec::new(),
+            encoding: Encoding::Utf8,
+            skip_rows: 0,
+            comment: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    Utf8,
+    /// UTF-8 with invalid bytes replaced by U+FFFD.
+    LossyUtf8,
+    /// ISO-8859-1, as produced by many Excel exports.
+    Latin1,
+}
+
+impl Encoding {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "utf8" | "utf-8" => Encoding::Utf8,
+            "utf8-lossy" | "lossy" => Encoding::LossyUtf8,
+            "latin1" | "latin-1" | "iso-8859-1" => Encoding::Latin1,
+            _ => return None,
+        })
+    }
+
+    /// The canonical name, as accepted by `from_name`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "utf8",
+            Encoding::LossyUtf8 => "utf8-lossy",
+            Encoding::Latin1 => "latin1",
+        }
+    }
+}
+
+impl From<&str> for Source {
+    fn from(path: &str) -> Self {
+        Source { paths: vec![path.to_string()], ..Default::default() }
+    }
+}
+
+/// Random subset of rows, e.g. `sample 1000 rows seed 42` or `sample 5%`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub size: SampleSize,
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SampleSize {
+    Rows(usize),
+    Percent(f64),
+}
+
+/// K-means grouping, e.g. `cluster by age, price into 4 as segment seed 7 with centroids`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cluster {
+    pub columns: Vec<String>,
+    pub k: usize,
+    pub alias: String,
+    pub seed: Option<u64>,
+    pub centroids: bool,
+}
+
+/// Long-to-wide reshape, e.g. `pivot sum(amount) by month for city`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pivot {
+    pub agg: AggFunc,
+    pub values: String,
+    pub columns: String,
+    pub index: Vec<String>,
+}
+
+/// Wide-to-long reshape, e.g. `unpivot jan, feb, mar into month, value`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unpivot {
+    pub columns: Vec<String>,
+    pub variable: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AggFunc {
+    Sum,
+    Mean,
+    Min,
+    Max,
+    Count,
+    First,
+    Last,
+    Median,
+}
+
+impl AggFunc {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "sum" => AggFunc::Sum,
+            "mean" | "avg" => AggFunc::Mean,
+            "min" => AggFunc::Min,
+            "max" => AggFunc::Max,
+            "count" => AggFunc::Count,
+            "first" => AggFunc::First,
+            "last" => AggFunc::Last,
+            "median" => AggFunc::Median,
+            _ => return None,
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AggFunc::Sum => "sum",
+            AggFunc::Mean => "mean",
+            AggFunc::Min => "min",
+            AggFunc::Max => "max",
+            AggFunc::Count => "count",
+            AggFunc::First => "first",
+            AggFunc::Last => "last",
+            AggFunc::Median => "median",
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -44,13 +373,27 @@ pub enum Value {
     Bool(bool),
 }
 
+impl Value {
+    /// Name of the literal's type, as used in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "integer",
+            Value::Float(_) => "float",
+            Value::String(_) => "string",
+            Value::Bool(_) => "boolean",
+        }
+    }
+}
+
 
 // pendent
-impl SortDirection {
-    pub fn from_str(s: &str) -> Self {
-        match s.to_ascii_lowercase().as_str() {
+impl std::str::FromStr for SortDirection {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_ascii_lowercase().as_str() {
             "desc" => SortDirection::Desc,
             _ => SortDirection::Asc,
-        }
+        })
     }
 }
@@ -1,16 +1,190 @@
-use clap::Parser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
 
-/// CLI arguments for the Query Compiler.
+/// CLI arguments for the CSV Query Next-Gen. Without a subcommand the arguments
+/// are those of `run`, so `csv-query --query q.query` runs a query.
 #[derive(Parser, Debug)]
-#[command(name = "csv-query", about = "Compile and execute .query files on CSV data")]
+#[command(name = "csv-query", about = "Compile and execute next-gen queries on CSV data.")]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct CliArgs {
-    pub query_path: PathBuf,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub run: RunArgs,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run a query and print or export its result.
+    Run(RunArgs),
+    /// Parse and analyze a query without running it.
+    Check(CheckArgs),
+    /// Rewrite query files in canonical style.
+    Fmt(FmtArgs),
+    /// Print the query plan, or draw it as a graph, without running the query.
+    Explain(ExplainArgs),
+    /// Print the columns and inferred types of a data file.
+    Schema(SchemaArgs),
+    /// Print the first rows of a data file.
+    Head(HeadArgs),
+    /// Type queries interactively, keeping sources loaded between them.
+    Repl,
+}
+
+/// Where the query text comes from.
+#[derive(Args, Debug)]
+pub struct QueryArgs {
+    /// Query file; `-` reads the query from stdin.
+    #[arg(short, long, required_unless_present = "eval", conflicts_with = "eval")]
+    pub query: Option<PathBuf>,
+
+    /// Query text, e.g. `-e 'source "x.csv" filter age > 3'`.
+    #[arg(short, long)]
+    pub eval: Option<String>,
+}
+
+/// Dumps of the compiler's intermediate results.
+#[derive(Args, Debug, Default)]
+pub struct DebugArgs {
+    /// Print the query's tokens.
+    #[arg(long, default_value_t = false)]
+    pub tokens: bool,
+
+    /// Print the parsed query.
+    #[arg(long, default_value_t = false)]
+    pub ast: bool,
+}
+
+/// How readable tables are cut.
+#[derive(Args, Debug)]
+pub struct TableArgs {
+    /// Rows shown by the markdown, html and table formats and `--show`.
+    #[arg(long)]
+    pub rows: Option<usize>,
+
+    /// Characters shown per cell by the markdown, html and table formats and `--show`.
+    #[arg(long)]
+    pub max_width: Option<usize>,
+
+    /// Show every row and full cell contents in table output.
+    #[arg(long, default_value_t = false)]
+    pub full: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct RunArgs {
+    #[command(flatten)]
+    pub query: QueryArgs,
+
+    /// File to write the result to; `-` writes to stdout.
+    #[arg(short, long)]
     pub output: Option<PathBuf>,
+
+    /// Output format: csv, json, ndjson, parquet, ipc, or markdown, html and table for
+    /// readable tables. Defaults to the output file's extension; without `--output`
+    /// the result goes to stdout.
+    #[arg(long)]
+    pub format: Option<String>,
+
+    #[command(flatten)]
+    pub table: TableArgs,
+
+    /// What to do if the output file exists: overwrite, append (CSV and NDJSON) or error_if_exists.
+    /// Overrides the query's `write to ... mode`.
+    #[arg(long)]
+    pub mode: Option<String>,
+
+    /// Write one file per group of these columns under the output directory
+    /// (`city=Recife/part.csv`); overrides the query's `write to ... partition by`.
+    #[arg(long, value_delimiter = ',')]
+    pub partition_by: Vec<String>,
+
+    /// CSV output field delimiter, e.g. `;`.
+    #[arg(long)]
+    pub delimiter: Option<char>,
+
+    /// Start CSV output with a UTF-8 byte order mark (for Excel).
+    #[arg(long, default_value_t = false)]
+    pub bom: bool,
+
+    /// When to quote CSV fields: necessary, always, non-numeric or never.
+    #[arg(long)]
+    pub quote_style: Option<String>,
+
+    /// Parquet/Arrow compression: uncompressed, snappy, gzip, lz4 or zstd.
+    #[arg(long)]
+    pub compression: Option<String>,
+
+    #[command(flatten)]
+    pub debug: DebugArgs,
+
+    /// Print the result as a table.
+    #[arg(long, default_value_t = false)]
+    pub show: bool,
+
+    /// Run on the streaming engine; used automatically for very large sources.
+    #[arg(long, default_value_t = false)]
+    pub streaming: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct CheckArgs {
+    #[command(flatten)]
+    pub query: QueryArgs,
+
+    #[command(flatten)]
+    pub debug: DebugArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct FmtArgs {
+    /// Query files to format in place; without any, or with `-`, formats stdin to stdout.
+    pub files: Vec<PathBuf>,
+
+    /// Don't write anything; fail if a file isn't formatted.
+    #[arg(long, default_value_t = false)]
+    pub check: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct ExplainArgs {
+    #[command(flatten)]
+    pub query: QueryArgs,
+
+    /// Render the optimized plan as a graph instead of describing it.
+    #[arg(long, value_enum)]
+    pub plan_format: Option<PlanFormat>,
+
+    /// With `--plan-format`, draw the parsed query's clauses instead of the plan.
+    #[arg(long, default_value_t = false)]
+    pub ast: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct SchemaArgs {
+    /// Data file; the format follows the extension, `-` reads CSV from stdin.
+    pub file: String,
+}
+
+#[derive(Args, Debug)]
+pub struct HeadArgs {
+    /// Data file; the format follows the extension, `-` reads CSV from stdin.
+    pub file: String,
+
+    /// Rows to print.
+    #[arg(short = 'n', long, default_value_t = 10)]
+    pub rows: usize,
+
+    /// Characters shown per cell.
+    #[arg(long)]
+    pub max_width: Option<usize>,
 }
 
-impl CliArgs {
-    pub fn parse() -> Self {
-        <Self as Parser>::parse()
-    }
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum PlanFormat {
+    /// Graphviz DOT.
+    Dot,
+    /// Mermaid flowchart.
+    Mermaid,
 }
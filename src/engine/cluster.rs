@@ -0,0 +1,146 @@
+use crate::ast::Cluster;
+use polars::prelude::*;
+use anyhow::Result;
+
+const MAX_ITERATIONS: usize = 100;
+
+/// Runs k-means over the chosen columns and appends the cluster id (and, if
+/// requested, the centroid coordinates) as new columns.
+/// Rows with a null in any of the columns are left unassigned.
+pub fn apply_cluster(mut df: DataFrame, cluster: Cluster) -> Result<DataFrame> {
+    let points = collect_points(&df, &cluster.columns)?;
+    let valid: Vec<&[f64]> = points.iter().flatten().map(Vec::as_slice).collect();
+    if valid.len() < cluster.k {
+        return Err(anyhow::anyhow!(
+            "Cannot build {} clusters from {} complete rows",
+            cluster.k,
+            valid.len()
+        ));
+    }
+
+    let mut rng = SplitMix64(cluster.seed.unwrap_or(0));
+    let centroids = kmeans(&valid, cluster.k, &mut rng);
+
+    let assignments: Vec<Option<u32>> = points
+        .iter()
+        .map(|p| p.as_ref().map(|p| nearest(p, &centroids).0 as u32))
+        .collect();
+
+    if cluster.centroids {
+        for (dim, column) in cluster.columns.iter().enumerate() {
+            let coords: Vec<Option<f64>> = assignments
+                .iter()
+                .map(|a| a.map(|c| centroids[c as usize][dim]))
+                .collect();
+            df.with_column(Series::new(&format!("{}_{}", cluster.alias, column), coords))?;
+        }
+    }
+    df.with_column(Series::new(&cluster.alias, assignments))?;
+    Ok(df)
+}
+
+/// Reads the cluster columns as f64 rows; `None` marks a row with a null value.
+fn collect_points(df: &DataFrame, columns: &[String]) -> Result<Vec<Option<Vec<f64>>>> {
+    let mut series = Vec::with_capacity(columns.len());
+    for name in columns {
+        let s = df.column(name)?;
+        if !s.dtype().is_numeric() {
+            return Err(anyhow::anyhow!("Cannot cluster on non-numeric column '{}'", name));
+        }
+        series.push(s.cast(&DataType::Float64)?);
+    }
+    let values: Vec<&Float64Chunked> = series.iter().map(|s| s.f64()).collect::<PolarsResult<_>>()?;
+
+    Ok((0..df.height())
+        .map(|row| values.iter().map(|ca| ca.get(row)).collect::<Option<Vec<f64>>>())
+        .collect())
+}
+
+/// Lloyd's algorithm seeded with k-means++ initialisation.
+fn kmeans(points: &[&[f64]], k: usize, rng: &mut SplitMix64) -> Vec<Vec<f64>> {
+    let mut centroids = init_centroids(points, k, rng);
+    let mut assignments = vec![usize::MAX; points.len()];
+
+    for _ in 0..MAX_ITERATIONS {
+        let mut changed = false;
+        for (i, p) in points.iter().enumerate() {
+            let (c, _) = nearest(p, &centroids);
+            if assignments[i] != c {
+                assignments[i] = c;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+
+        let dims = points[0].len();
+        let mut sums = vec![vec![0.0; dims]; k];
+        let mut counts = vec![0usize; k];
+        for (p, &c) in points.iter().zip(&assignments) {
+            counts[c] += 1;
+            for (sum, v) in sums[c].iter_mut().zip(p.iter()) {
+                *sum += v;
+            }
+        }
+        // Empty clusters keep their previous centroid.
+        for ((centroid, sum), count) in centroids.iter_mut().zip(sums).zip(counts) {
+            if count > 0 {
+                *centroid = sum.into_iter().map(|v| v / count as f64).collect();
+            }
+        }
+    }
+    centroids
+}
+
+fn init_centroids(points: &[&[f64]], k: usize, rng: &mut SplitMix64) -> Vec<Vec<f64>> {
+    let mut centroids = vec![points[rng.below(points.len())].to_vec()];
+    while centroids.len() < k {
+        let weights: Vec<f64> = points.iter().map(|p| nearest(p, &centroids).1).collect();
+        let total: f64 = weights.iter().sum();
+        let next = if total == 0.0 {
+            rng.below(points.len())
+        } else {
+            let mut target = rng.unit() * total;
+            weights
+                .iter()
+                .position(|w| {
+                    target -= w;
+                    target <= 0.0
+                })
+                .unwrap_or(points.len() - 1)
+        };
+        centroids.push(points[next].to_vec());
+    }
+    centroids
+}
+
+/// Returns the index of the closest centroid and the squared distance to it.
+fn nearest(point: &[f64], centroids: &[Vec<f64>]) -> (usize, f64) {
+    centroids
+        .iter()
+        .map(|c| c.iter().zip(point).map(|(a, b)| (a - b) * (a - b)).sum::<f64>())
+        .enumerate()
+        .fold((0, f64::INFINITY), |best, (i, d)| if d < best.1 { (i, d) } else { best })
+}
+
+/// Small deterministic PRNG so results only depend on the query's seed.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
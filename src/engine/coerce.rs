@@ -0,0 +1,90 @@
+use crate::ast::Value;
+use polars::export::chrono::{NaiveDate, NaiveDateTime};
+use polars::prelude::*;
+use super::schema::parse_bool;
+
+/// Prepares both sides of `column <op> value` so Polars compares like with like.
+///
+/// - Numbers widen: integer columns against a float compare as floats, and
+///   any integer literal against a float column becomes a float.
+/// - Quoted numbers (`age > "30"`) are parsed for numeric columns.
+/// - Strings are parsed as `YYYY-MM-DD` for date columns, and also with a
+///   `HH:MM:SS` time (space or `T` separated) for datetime columns.
+/// - Boolean columns accept `true`/`false`, `yes`/`no`, `t`/`f`, `y`/`n` and `1`/`0`,
+///   quoted or not.
+///
+/// Anything else is an error describing why the value doesn't fit.
+pub fn coerce_comparison(column: Expr, dtype: &DataType, value: &Value) -> Result<(Expr, Expr), String> {
+    match (dtype, value) {
+        (DataType::Null | DataType::Unknown, value) => Ok((column, plain_literal(value))),
+
+        (dtype, Value::String(s)) if dtype.is_numeric() => match parse_number(s) {
+            Some(number) => coerce_comparison(column, dtype, &number),
+            None => Err(format!("\"{}\" is not a number", s)),
+        },
+        (dtype, Value::Number(n)) if dtype.is_float() => Ok((column, lit(*n as f64))),
+        (dtype, Value::Number(n)) if dtype.is_numeric() => Ok((column, lit(*n))),
+        (dtype, Value::Float(f)) if dtype.is_float() => Ok((column, lit(*f))),
+        (dtype, Value::Float(f)) if dtype.is_numeric() => Ok((column.cast(DataType::Float64), lit(*f))),
+
+        (DataType::String, Value::String(s)) => Ok((column, lit(s.as_str()))),
+
+        (DataType::Boolean, Value::Bool(b)) => Ok((column, lit(*b))),
+        (DataType::Boolean, Value::String(s)) => match parse_bool(s) {
+            Some(b) => Ok((column, lit(b))),
+            None => Err(format!("\"{}\" is not a boolean", s)),
+        },
+        (DataType::Boolean, Value::Number(n @ (0 | 1))) => Ok((column, lit(*n == 1))),
+
+        (DataType::Date, Value::String(s)) => match NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            Ok(date) => {
+                let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap_or_default();
+                let days = (date - epoch).num_days() as i32;
+                Ok((column, lit(days).cast(DataType::Date)))
+            }
+            Err(_) => Err(format!("\"{}\" is not a date (expected YYYY-MM-DD)", s)),
+        },
+        (DataType::Datetime(unit, zone), Value::String(s)) => match parse_datetime(s) {
+            Some(datetime) => {
+                let utc = datetime.and_utc();
+                let ticks = match unit {
+                    TimeUnit::Nanoseconds => utc.timestamp_nanos_opt().ok_or_else(|| {
+                        format!("\"{}\" is outside the range of nanosecond datetimes (years 1677 to 2262)", s)
+                    })?,
+                    TimeUnit::Microseconds => utc.timestamp_micros(),
+                    TimeUnit::Milliseconds => utc.timestamp_millis(),
+                };
+                Ok((column, lit(ticks).cast(DataType::Datetime(*unit, zone.clone()))))
+            }
+            None => Err(format!("\"{}\" is not a datetime (expected YYYY-MM-DD[ HH:MM:SS])", s)),
+        },
+
+        (dtype, value) => Err(format!("no implicit conversion from {} to {}", value.type_name(), dtype)),
+    }
+}
+
+/// A literal as written, for columns whose type isn't known up front.
+pub fn plain_literal(value: &Value) -> Expr {
+    match value {
+        Value::Number(n) => lit(*n),
+        Value::Float(f) => lit(*f),
+        Value::String(s) => lit(s.as_str()),
+        Value::Bool(b) => lit(*b),
+    }
+}
+
+fn parse_number(s: &str) -> Option<Value> {
+    let s = s.trim();
+    s.parse()
+        .map(Value::Number)
+        .ok()
+        .or_else(|| s.parse().ok().filter(|f: &f64| f.is_finite()).map(Value::Float))
+}
+
+pub(super) fn parse_datetime(s: &str) -> Option<NaiveDateTime> {
+    let s = s.trim();
+    ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+        .or_else(|| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()?.and_hms_opt(0, 0, 0))
+}
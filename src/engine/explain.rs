@@ -0,0 +1,94 @@
+use crate::ast::Query;
+use crate::plan::{lower, optimize, LogicalPlan};
+use super::{to_lazy, LoadedSources};
+use anyhow::Result;
+use std::fmt::Write;
+
+/// Describes how a query would run without running it: the lowered plan,
+/// the plan after our rewrites, the plan Polars will execute and, per file
+/// scan, how many columns are read and which pushdowns Polars applied.
+pub fn explain(query: Query) -> Result<String> {
+    explain_with(query, &LoadedSources::new())
+}
+
+/// Like `explain`, for a query whose sources in `loaded` are taken from memory.
+pub fn explain_with(query: Query, loaded: &LoadedSources) -> Result<String> {
+    let logical = lower(query)?;
+    let optimized = optimize(logical.clone());
+
+    // Sampling, clustering and pivoting need their input collected, so only
+    // the part of the plan below them can be described without running it.
+    let (lazy_part, barrier) = match materialised_input(&optimized) {
+        Some((input, stage)) => (input.clone(), Some(stage)),
+        None => (optimized.clone(), None),
+    };
+    let physical = to_lazy(lazy_part, false, loaded)?.describe_optimized_plan()?;
+
+    let mut out = String::new();
+    writeln!(out, "== Logical plan ==\n{}", logical)?;
+    writeln!(out, "== Optimized plan ==\n{}", optimized)?;
+    writeln!(out, "== Physical plan (Polars) ==")?;
+    if let Some(stage) = barrier {
+        writeln!(out, "(input of `{}`; the stages above it run on the collected result)", stage)?;
+    }
+    writeln!(out, "{}\n", physical.trim_end())?;
+    writeln!(out, "== Scans ==")?;
+    for scan in summarise_scans(&physical) {
+        writeln!(out, "{}", scan)?;
+    }
+    Ok(out)
+}
+
+/// The input of the innermost stage that materialises its input, if any.
+fn materialised_input(plan: &LogicalPlan) -> Option<(&LogicalPlan, &'static str)> {
+    let inner = plan.inputs().into_iter().find_map(materialised_input);
+    inner.or(match plan {
+        LogicalPlan::Sample { input, .. } => Some((input, "sample")),
+        LogicalPlan::Cluster { input, .. } => Some((input, "cluster")),
+        LogicalPlan::Pivot { input, .. } => Some((input, "pivot")),
+        _ => None,
+    })
+}
+
+/// Pulls the per-scan details out of Polars' plan description.
+/// File scans print `<format> SCAN <path>` followed by one detail per line
+/// (`Anonymous` for files the lazy scanners can't read); sources loaded into
+/// memory print `DF [columns]; PROJECT ...; SELECTION: ...` on one line.
+fn summarise_scans(physical: &str) -> Vec<String> {
+    let mut scans: Vec<(String, Vec<String>)> = Vec::new();
+    for line in physical.lines().map(str::trim) {
+        if let Some(path) = line.strip_prefix("Anonymous SCAN ") {
+            scans.push((path.to_string(), vec!["read whole when the query runs (no pushdown)".to_string()]));
+        } else if let Some((_, path)) = line.split_once(" SCAN ") {
+            scans.push((path.to_string(), Vec::new()));
+        } else if let Some(rest) = line.strip_prefix("DF ") {
+            let mut parts = rest.split("; ");
+            parts.next();
+            let details = parts.filter_map(describe_detail).collect();
+            scans.push(("in-memory source".to_string(), details));
+        } else if let Some((_, details)) = scans.last_mut()
+            && let Some(detail) = describe_detail(line)
+        {
+            details.push(detail);
+        }
+    }
+
+    scans
+        .into_iter()
+        .map(|(name, details)| format!("{}: {}", name, details.join(", ")))
+        .collect()
+}
+
+fn describe_detail(line: &str) -> Option<String> {
+    if let Some(columns) = line.strip_prefix("PROJECT ").and_then(|l| l.strip_suffix(" COLUMNS")) {
+        return Some(match columns.strip_prefix("*/") {
+            Some(total) => format!("reads all {} columns", total),
+            None => format!("reads {} columns (projection pushed down)", columns),
+        });
+    }
+    if let Some(selection) = line.strip_prefix("SELECTION: ") {
+        let pushed = !matches!(selection.trim_matches('"'), "None" | "");
+        return pushed.then(|| "filter pushed down".to_string());
+    }
+    line.strip_prefix("N_ROWS: ").map(|n| format!("stops after {} rows (limit pushed down)", n))
+}
@@ -1,105 +1,175 @@
-use crate::ast::{Query, Expr, Value, SortDirection};
+mod cluster;
+mod coerce;
+mod explain;
+mod reshape;
+mod schema;
+mod source;
+mod stream;
+
+use crate::ast::{Query, Source, Value, SortDirection, Sample, SampleSize, AggFunc};
+use crate::plan::{lower, optimize, CompareOp, JoinKind, LogicalPlan, PlanExpr};
 use polars::prelude::*;
 use anyhow::Result;
+use cluster::apply_cluster;
+use coerce::plain_literal;
+use reshape::{apply_pivot, apply_unpivot};
+use source::scan_source;
 
-pub fn execute_query(query: Query) -> Result<DataFrame> {
-    let mut df = CsvReader::from_path(&query.source)?
-        .infer_schema(None)
-        .has_header(true)
-        .finish()?;
+pub use coerce::coerce_comparison;
+pub use explain::{explain, explain_with};
+pub use source::{LoadedSources, SOURCE_FILE_COLUMN};
+pub use stream::{execute_query_streaming, sink_query, should_stream, source_size, STREAMING_THRESHOLD_BYTES};
 
-    if let Some(expr) = query.filter {
-        df = apply_filter(df, expr)?;
-    }
-
-    if !query.show.is_empty() {
-        df = df.select(&query.show)?;
-    }
-
-    if let Some((field, expr)) = query.map {
-        let mut df2 = df.clone();
-        let mut new_col = eval_map_expr(&df, expr)?;
-        new_col.rename(&field);
-        df2.with_column(new_col)?;
-        df = df2;
-    }
+/// Runs a query and materialises the result.
+pub fn execute_query(query: Query) -> Result<DataFrame> {
+    execute_query_with(query, &LoadedSources::new())
+}
 
-    if query.unique {
-        df = df.unique(None, UniqueKeepStrategy::First, None)?;
-    }
+/// Runs a query, taking the sources in `loaded` from memory instead of their files.
+pub fn execute_query_with(query: Query, loaded: &LoadedSources) -> Result<DataFrame> {
+    Ok(plan(query, false, loaded)?.collect()?)
+}
 
-    if let Some(sort) = query.sort {
-        let descending = matches!(sort.direction, SortDirection::Desc);
-        let options = SortMultipleOptions {
-            descending: vec![descending],
-            ..Default::default()
-        };
-        df = df.sort(&[sort.column.as_str()], options)?;
-    }
+/// Runs an already lowered (and possibly optimized) logical plan.
+pub fn execute_plan(plan: LogicalPlan) -> Result<DataFrame> {
+    Ok(to_lazy(plan, false, &LoadedSources::new())?.collect()?)
+}
 
-    if let Some(cap) = query.cap {
-        let h = df.height();
-        let cap = cap.min(h);
-        df = df.head(Some(cap));
-    }
+/// Lowers and optimizes a query, then translates the plan into a Polars
+/// `LazyFrame` without running it. Polars further pushes filters and
+/// projections into the scan and turns `sort` + `cap` into a top-k. Stages
+/// Polars can't express lazily (sampling, clustering, pivoting) materialise
+/// their input first.
+pub fn build_lazy(query: Query) -> Result<LazyFrame> {
+    plan(query, false, &LoadedSources::new())
+}
 
-    Ok(df)
+/// Column names and types of a source as they come out of the scan, without
+/// running it: declared columns take their declared types, and the rest are
+/// inferred from the first rows of CSV files or from the file metadata. JSON
+/// arrays and stdin have no cheaper way and are read whole.
+pub fn source_schema(source: &Source) -> Result<Schema> {
+    Ok(scan_source(source, &LoadedSources::new())?.schema()?.as_ref().clone())
 }
 
-fn apply_filter(df: DataFrame, expr: Expr) -> Result<DataFrame> {
-    let mask = eval_expr(&df, expr)?.bool()?.clone();
-    Ok(df.filter(&mask)?)
+/// Builds the `LazyFrame`; `streaming` also applies to the intermediate collects.
+fn plan(query: Query, streaming: bool, loaded: &LoadedSources) -> Result<LazyFrame> {
+    to_lazy(optimize(lower(query)?), streaming, loaded)
 }
 
-fn eval_expr(df: &DataFrame, expr: Expr) -> Result<Series> {
-    use Expr::*;
-    match expr {
-        Eq(col, val) => Ok(df.column(&col)?.equal(&value_series(df, &col, val)?)?.into_series()),
-        NotEq(col, val) => Ok(df.column(&col)?.not_equal(&value_series(df, &col, val)?)?.into_series()),
-        Gt(col, val) => Ok(df.column(&col)?.gt(&value_series(df, &col, val)?)?.into_series()),
-        Lt(col, val) => Ok(df.column(&col)?.lt(&value_series(df, &col, val)?)?.into_series()),
-        Gte(col, val) => Ok(df.column(&col)?.gt_eq(&value_series(df, &col, val)?)?.into_series()),
-        Lte(col, val) => Ok(df.column(&col)?.lt_eq(&value_series(df, &col, val)?)?.into_series()),
-        And(lhs, rhs) => {
-            let l = eval_expr(df, *lhs)?.bool()?.clone();
-            let r = eval_expr(df, *rhs)?.bool()?.clone();
-            Ok((l & r).into_series())
+fn to_lazy(plan: LogicalPlan, streaming: bool, loaded: &LoadedSources) -> Result<LazyFrame> {
+    Ok(match plan {
+        LogicalPlan::Scan { source } => scan_source(&source, loaded)?,
+        LogicalPlan::Filter { input, predicate } => {
+            let lf = to_lazy(*input, streaming, loaded)?;
+            let schema = lf.schema()?;
+            let predicate = to_polars_expr(predicate, schema.as_ref())?;
+            lf.filter(predicate)
         }
-        Or(lhs, rhs) => {
-            let l = eval_expr(df, *lhs)?.bool()?.clone();
-            let r = eval_expr(df, *rhs)?.bool()?.clone();
-            Ok((l | r).into_series())
+        LogicalPlan::Project { input, columns } => {
+            to_lazy(*input, streaming, loaded)?.select(columns.iter().map(|name| col(name)).collect::<Vec<_>>())
         }
-        MapExpr(_, _) => Err(anyhow::anyhow!("MapExpr is not allowed in filter")),
-    }
+        LogicalPlan::Map { input, name, expr } => {
+            let lf = to_lazy(*input, streaming, loaded)?;
+            let schema = lf.schema()?;
+            let expr = to_polars_expr(expr, schema.as_ref())?;
+            lf.with_column(expr.alias(&name))
+        }
+        LogicalPlan::Aggregate { input, keys, aggregations } => {
+            let keys: Vec<Expr> = keys.iter().map(|name| col(name)).collect();
+            let aggs: Vec<Expr> = aggregations
+                .iter()
+                .map(|a| aggregate(a.func, &a.column).alias(&a.alias))
+                .collect();
+            to_lazy(*input, streaming, loaded)?.group_by_stable(keys).agg(aggs)
+        }
+        LogicalPlan::Sort { input, sort } => {
+            let options = SortMultipleOptions {
+                descending: vec![matches!(sort.direction, SortDirection::Desc)],
+                maintain_order: true,
+                ..Default::default()
+            };
+            to_lazy(*input, streaming, loaded)?.sort([sort.column.as_str()], options)
+        }
+        LogicalPlan::Limit { input, n } => to_lazy(*input, streaming, loaded)?.limit(n as IdxSize),
+        LogicalPlan::Distinct { input } => to_lazy(*input, streaming, loaded)?.unique_stable(None, UniqueKeepStrategy::First),
+        LogicalPlan::Join { left, right, on, kind } => {
+            let on: Vec<Expr> = on.iter().map(|name| col(name)).collect();
+            let how = match kind {
+                JoinKind::Inner => JoinType::Inner,
+                JoinKind::Left => JoinType::Left,
+            };
+            to_lazy(*left, streaming, loaded)?.join(to_lazy(*right, streaming, loaded)?, &on, &on, JoinArgs::new(how))
+        }
+        LogicalPlan::Sample { input, sample } => {
+            apply_sample(to_lazy(*input, streaming, loaded)?.with_streaming(streaming).collect()?, sample)?.lazy()
+        }
+        LogicalPlan::Cluster { input, cluster } => {
+            apply_cluster(to_lazy(*input, streaming, loaded)?.with_streaming(streaming).collect()?, cluster)?.lazy()
+        }
+        LogicalPlan::Pivot { input, pivot } => {
+            apply_pivot(to_lazy(*input, streaming, loaded)?.with_streaming(streaming).collect()?, pivot)?.lazy()
+        }
+        LogicalPlan::Unpivot { input, unpivot } => apply_unpivot(to_lazy(*input, streaming, loaded)?, unpivot)?,
+    })
 }
 
-fn value_series(_df: &DataFrame, col: &str, val: Value) -> Result<Series> {
-    Ok(match val {
-        Value::Number(n) => Series::new(col, vec![n]),
-        Value::Float(f) => Series::new(col, vec![f]),
-        Value::String(s) => Series::new(col, vec![s]),
-        Value::Bool(b) => Series::new(col, vec![b]),
-    })
+fn apply_sample(df: DataFrame, sample: Sample) -> Result<DataFrame> {
+    let n = match sample.size {
+        SampleSize::Rows(n) => n.min(df.height()),
+        SampleSize::Percent(p) => (df.height() as f64 * p / 100.0) as usize,
+    };
+    Ok(df.sample_n_literal(n, false, false, sample.seed)?)
 }
 
-fn eval_map_expr(df: &DataFrame, expr: Expr) -> Result<Series> {
-    match expr {
-        Expr::Eq(field, value) => Ok(df.column(&field)?.equal(&value_series(df, &field, value)?)?.into_series()),
-        Expr::Gt(field, value) => Ok(df.column(&field)?.gt(&value_series(df, &field, value)?)?.into_series()),
-        Expr::Lt(field, value) => Ok(df.column(&field)?.lt(&value_series(df, &field, value)?)?.into_series()),
-        Expr::And(lhs, rhs) => {
-            let l = eval_map_expr(df, *lhs)?.bool()?.clone();
-            let r = eval_map_expr(df, *rhs)?.bool()?.clone();
-            Ok((l & r).into_series())
-        }
-        Expr::Or(lhs, rhs) => {
-            let l = eval_map_expr(df, *lhs)?.bool()?.clone();
-            let r = eval_map_expr(df, *rhs)?.bool()?.clone();
-            Ok((l | r).into_series())
-        }
-        Expr::NotEq(_, _) | Expr::Gte(_, _) | Expr::Lte(_, _) | Expr::MapExpr(_, _) => {
-            Err(anyhow::anyhow!("Map expressions not fully supported yet"))
+/// Translates a plan expression into a Polars expression, coercing literals
+/// compared with a column to that column's type (see `coerce_comparison`).
+fn to_polars_expr(expr: PlanExpr, schema: &Schema) -> Result<Expr> {
+    Ok(match expr {
+        PlanExpr::Column(name) => col(&name),
+        PlanExpr::Literal(value) => plain_literal(&value),
+        PlanExpr::Compare(op, lhs, rhs) => {
+            let (lhs, rhs) = match (*lhs, *rhs) {
+                (PlanExpr::Column(name), PlanExpr::Literal(value)) => coerce(&name, &value, schema)?,
+                (PlanExpr::Literal(value), PlanExpr::Column(name)) => {
+                    let (column, value) = coerce(&name, &value, schema)?;
+                    (value, column)
+                }
+                (lhs, rhs) => (to_polars_expr(lhs, schema)?, to_polars_expr(rhs, schema)?),
+            };
+            match op {
+                CompareOp::Eq => lhs.eq(rhs),
+                CompareOp::NotEq => lhs.neq(rhs),
+                CompareOp::Gt => lhs.gt(rhs),
+                CompareOp::Lt => lhs.lt(rhs),
+                CompareOp::Gte => lhs.gt_eq(rhs),
+                CompareOp::Lte => lhs.lt_eq(rhs),
+            }
         }
+        PlanExpr::And(lhs, rhs) => to_polars_expr(*lhs, schema)?.and(to_polars_expr(*rhs, schema)?),
+        PlanExpr::Or(lhs, rhs) => to_polars_expr(*lhs, schema)?.or(to_polars_expr(*rhs, schema)?),
+    })
+}
+
+fn coerce(name: &str, value: &Value, schema: &Schema) -> Result<(Expr, Expr)> {
+    match schema.get(name) {
+        Some(dtype) => coerce_comparison(col(name), dtype, value)
+            .map_err(|reason| anyhow::anyhow!("Cannot compare column '{}' ({}): {}", name, dtype, reason)),
+        None => Ok((col(name), plain_literal(value))),
+    }
+}
+
+/// Aggregates a column within each group.
+fn aggregate(func: AggFunc, column: &str) -> Expr {
+    let values = col(column);
+    match func {
+        AggFunc::Sum => values.sum(),
+        AggFunc::Mean => values.mean(),
+        AggFunc::Min => values.min(),
+        AggFunc::Max => values.max(),
+        AggFunc::Count => values.count(),
+        AggFunc::First => values.first(),
+        AggFunc::Last => values.last(),
+        AggFunc::Median => values.median(),
     }
 }
@@ -0,0 +1,41 @@
+use crate::ast::{Pivot, Unpivot};
+use super::aggregate;
+use polars::lazy::frame::pivot::pivot_stable;
+use polars::prelude::*;
+use anyhow::Result;
+
+/// Spreads the distinct values of `pivot.columns` into new columns, one row per index group.
+pub fn apply_pivot(df: DataFrame, spec: Pivot) -> Result<DataFrame> {
+    let agg = aggregate(spec.agg, &spec.values);
+    Ok(pivot_stable(
+        &df,
+        &spec.index,
+        [&spec.columns],
+        Some([&spec.values]),
+        true,
+        Some(agg),
+        None,
+    )?)
+}
+
+/// Stacks the listed columns into `variable`/`value` pairs, keeping every other column as an id.
+pub fn apply_unpivot(lf: LazyFrame, spec: Unpivot) -> Result<LazyFrame> {
+    let schema = lf.schema()?;
+    for name in &spec.columns {
+        if schema.get(name).is_none() {
+            return Err(anyhow::anyhow!("not found: {}", name));
+        }
+    }
+    let id_vars = schema
+        .iter_names()
+        .filter(|name| !spec.columns.iter().any(|c| c == name.as_str()))
+        .cloned()
+        .collect();
+    Ok(lf.melt(MeltArgs {
+        id_vars,
+        value_vars: spec.columns.iter().map(|c| c.as_str().into()).collect(),
+        variable_name: Some(spec.variable.as_str().into()),
+        value_name: Some(spec.value.as_str().into()),
+        streamable: false,
+    }))
+}
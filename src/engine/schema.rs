@@ -0,0 +1,109 @@
+use crate::ast::{ColumnDef, ColumnType, SourceSchema};
+use super::coerce::parse_datetime;
+use polars::prelude::*;
+use anyhow::Result;
+
+/// Row number column (1-based) a scan adds for `conform`, so a value that
+/// doesn't fit its declared type can be reported with its row.
+pub const ROW_COLUMN: &str = "__row";
+
+/// Casts the declared columns of a scanned file to their declared types. The
+/// scan must carry a `ROW_COLUMN`. Missing columns and `exact` mismatches fail
+/// here, from the scan's schema; a value that doesn't fit fails the query when
+/// it runs, naming the column, row and value. The casts stay lazy, so filters
+/// and projections on other columns still reach the scan.
+pub fn conform(lf: LazyFrame, schema: &SourceSchema, path: &str) -> Result<LazyFrame> {
+    let actual = lf.schema()?;
+    let actual: Vec<&str> = actual.iter_names().map(|name| name.as_str()).filter(|name| *name != ROW_COLUMN).collect();
+    if schema.exact {
+        let declared: Vec<&str> = schema.columns.iter().map(|c| c.name.as_str()).collect();
+        if declared.len() != actual.len() || declared.iter().any(|name| !actual.contains(name)) {
+            return Err(anyhow::anyhow!(
+                "'{}' has columns [{}] but the exact schema declares [{}]",
+                path,
+                actual.join(", "),
+                declared.join(", ")
+            ));
+        }
+    }
+    if let Some(missing) = schema.columns.iter().find(|column| !actual.contains(&column.name.as_str())) {
+        return Err(anyhow::anyhow!("Declared column '{}' not found in '{}'", missing.name, path));
+    }
+
+    let casts: Vec<Expr> = schema.columns.iter().map(|column| checked_cast(column, path)).collect();
+    Ok(lf.with_columns(casts).drop([ROW_COLUMN]))
+}
+
+/// Casts a column, failing on the first value that doesn't fit.
+fn checked_cast(column: &ColumnDef, path: &str) -> Expr {
+    let (name, ty, path) = (column.name.clone(), column.ty, path.to_string());
+    as_struct(vec![col(ROW_COLUMN), col(&column.name)])
+        .map(
+            move |rows_and_values| {
+                let fields = rows_and_values.struct_()?.fields();
+                let (rows, values) = (&fields[0], &fields[1]);
+                let cast = cast_column(values, ty)?;
+
+                // A non-strict cast turns unparsable values into nulls; report the first one.
+                if cast.null_count() > values.null_count()
+                    && let Some((row, value)) = rows
+                        .iter()
+                        .zip(values.iter().zip(cast.iter()))
+                        .find(|(_, (before, after))| !before.is_null() && after.is_null())
+                        .map(|(row, (before, _))| (row, before))
+                {
+                    polars_bail!(ComputeError:
+                        "Column '{}' in '{}' is declared as {:?} but row {} has value {}", name, path, ty, row, value
+                    );
+                }
+                Ok(Some(cast.with_name(&name)))
+            },
+            GetOutput::from_type(to_dtype(column.ty)),
+        )
+        .alias(&column.name)
+}
+
+pub fn to_dtype(ty: ColumnType) -> DataType {
+    match ty {
+        ColumnType::String => DataType::String,
+        ColumnType::Int => DataType::Int64,
+        ColumnType::Float => DataType::Float64,
+        ColumnType::Bool => DataType::Boolean,
+        ColumnType::Date => DataType::Date,
+        ColumnType::Datetime => DataType::Datetime(TimeUnit::Microseconds, None),
+    }
+}
+
+fn cast_column(series: &Series, ty: ColumnType) -> PolarsResult<Series> {
+    let target = to_dtype(ty);
+    if series.dtype() == &target {
+        return Ok(series.clone());
+    }
+    // Polars has no string-to-boolean cast, so parse the usual spellings by hand.
+    if ty == ColumnType::Bool && series.dtype() == &DataType::String {
+        let parsed: BooleanChunked = series
+            .str()?
+            .into_iter()
+            .map(|v| v.and_then(parse_bool))
+            .collect();
+        return Ok(parsed.with_name(series.name()).into_series());
+    }
+    // Polars only casts the `T` form, so accept the same formats as query literals.
+    if ty == ColumnType::Datetime && series.dtype() == &DataType::String {
+        let parsed: Int64Chunked = series
+            .str()?
+            .into_iter()
+            .map(|v| v.and_then(parse_datetime).map(|dt| dt.and_utc().timestamp_micros()))
+            .collect();
+        return Ok(parsed.with_name(series.name()).into_datetime(TimeUnit::Microseconds, None).into_series());
+    }
+    series.cast(&target)
+}
+
+pub(super) fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "true" | "t" | "yes" | "y" | "1" => Some(true),
+        "false" | "f" | "no" | "n" | "0" => Some(false),
+        _ => None,
+    }
+}
@@ -0,0 +1,269 @@
+use crate::ast::{CsvOptions, Encoding, FileFormat, Source, STDIN_PATH};
+use super::schema::{conform, ROW_COLUMN};
+use polars::io::mmap::MmapBytesReader;
+use polars::prelude::*;
+use anyhow::Result;
+use std::fs::File;
+use std::any::Any;
+use std::io::{Cursor, Read};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// Name of the column added by `with source_file`.
+pub const SOURCE_FILE_COLUMN: &str = "_source_file";
+
+/// Rows read to infer the column types of CSV files.
+const INFER_SCHEMA_ROWS: usize = 10_000;
+
+/// Sources already read into memory, e.g. by the REPL, so later queries over
+/// them don't read the files again. Queries run with `execute_query_with` use
+/// these frames in place of the files.
+#[derive(Debug, Clone, Default)]
+pub struct LoadedSources {
+    frames: Vec<(Source, DataFrame)>,
+}
+
+impl LoadedSources {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads a source into memory unless it is already loaded, and returns its data.
+    pub fn load(&mut self, source: &Source) -> Result<&DataFrame> {
+        let index = match self.frames.iter().position(|(known, _)| known == source) {
+            Some(index) => index,
+            None => {
+                let df = scan_source(source, &LoadedSources::new())?.collect()?;
+                self.frames.push((source.clone(), df));
+                self.frames.len() - 1
+            }
+        };
+        Ok(&self.frames[index].1)
+    }
+
+    pub fn get(&self, source: &Source) -> Option<&DataFrame> {
+        self.frames.iter().find(|(known, _)| known == source).map(|(_, df)| df)
+    }
+
+    /// The loaded sources and their data, in the order they were loaded.
+    pub fn iter(&self) -> impl Iterator<Item = (&Source, &DataFrame)> {
+        self.frames.iter().map(|(source, df)| (source, df))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Forgets every loaded source, so the next query reads the files again.
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+}
+
+/// Scans every file matched by the source paths and stacks them vertically.
+/// Columns are aligned by name; columns missing from a file are filled with nulls.
+/// A source in `loaded` is taken from memory instead.
+pub fn scan_source(source: &Source, loaded: &LoadedSources) -> Result<LazyFrame> {
+    if let Some(df) = loaded.get(source) {
+        return Ok(df.clone().lazy());
+    }
+    let files = expand_paths(&source.paths)?;
+
+    let mut frames = Vec::with_capacity(files.len());
+    for file in &files {
+        let format = source.format.unwrap_or_else(|| FileFormat::from_path(file));
+        let mut lf = scan_file(file, format, source)?;
+        if source.tag_file {
+            lf = lf.with_column(lit(file.as_str()).alias(SOURCE_FILE_COLUMN));
+        }
+        frames.push(lf);
+    }
+
+    if frames.len() == 1 {
+        return Ok(frames.remove(0));
+    }
+    let args = UnionArgs { to_supertypes: true, ..Default::default() };
+    Ok(concat_lf_diagonal(frames, args)?)
+}
+
+fn scan_file(path: &str, format: FileFormat, source: &Source) -> Result<LazyFrame> {
+    let lazy = match (format, source.options.encoding) {
+        _ if path == STDIN_PATH => eager_scan(path, format, source),
+        (FileFormat::Csv, Encoding::Latin1) | (FileFormat::Json, _) => eager_scan(path, format, source),
+        (FileFormat::Csv, _) => scan_csv(path, &source.options, &text_columns(source)),
+        (FileFormat::Parquet, _) => LazyFrame::scan_parquet(path, ScanArgsParquet::default()),
+        (FileFormat::Ndjson, _) => LazyJsonLineReader::new(path).finish(),
+        (FileFormat::Ipc, _) => LazyFrame::scan_ipc(path, ScanArgsIpc::default()),
+    };
+    let lf = lazy.map_err(|e| anyhow::anyhow!("Failed to read '{}': {}", path, e))?;
+    match &source.schema {
+        Some(schema) => conform(lf.with_row_index(ROW_COLUMN, Some(1)), schema, path),
+        None => Ok(lf),
+    }
+}
+
+fn scan_csv(path: &str, options: &CsvOptions, dtypes: &Schema) -> PolarsResult<LazyFrame> {
+    LazyCsvReader::new(path)
+        .with_infer_schema_length(Some(INFER_SCHEMA_ROWS))
+        .with_dtype_overwrite((!dtypes.is_empty()).then_some(dtypes))
+        .has_header(options.header)
+        .with_separator(options.delimiter)
+        .with_quote_char(options.quote)
+        .with_null_values(null_values(options))
+        .with_encoding(csv_encoding(options))
+        .with_skip_rows(options.skip_rows)
+        .with_comment_prefix(options.comment.as_deref())
+        .finish()
+}
+
+/// Declared columns, typed as text: CSV readers take them as strings so
+/// inference can't mangle them (e.g. leading zeros) before `conform` casts them.
+fn text_columns(source: &Source) -> Schema {
+    source
+        .schema
+        .iter()
+        .flat_map(|schema| &schema.columns)
+        .map(|column| Field::new(&column.name, DataType::String))
+        .collect()
+}
+
+/// A file the lazy scanners can't read (stdin, Latin-1 CSV, JSON arrays). It
+/// is read whole only when the query runs; its schema comes from the first
+/// `INFER_SCHEMA_ROWS` rows for CSV, but needs a full read for the other formats.
+struct EagerScan {
+    path: String,
+    format: FileFormat,
+    source: Source,
+}
+
+impl AnonymousScan for EagerScan {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn scan(&self, _args: AnonymousScanArgs) -> PolarsResult<DataFrame> {
+        read_file(&self.path, self.format, &self.source, None).map_err(|e| polars_err!(ComputeError: "{}", e))
+    }
+
+    fn schema(&self, _infer_schema_length: Option<usize>) -> PolarsResult<SchemaRef> {
+        let prefix = read_file(&self.path, self.format, &self.source, Some(INFER_SCHEMA_ROWS))
+            .map_err(|e| polars_err!(ComputeError: "{}", e))?;
+        Ok(prefix.schema().into())
+    }
+}
+
+fn eager_scan(path: &str, format: FileFormat, source: &Source) -> PolarsResult<LazyFrame> {
+    let scan = EagerScan { path: path.to_string(), format, source: source.clone() };
+    let args = ScanArgsAnonymous { name: "EAGER SCAN", ..Default::default() };
+    let mut lf = LazyFrame::anonymous_scan(Arc::new(scan), args)?;
+    // Anonymous scans have no path; set it so `explain` can name the file.
+    if let LogicalPlan::Scan { paths, .. } = &mut lf.logical_plan {
+        *paths = Arc::new([PathBuf::from(path)]);
+    }
+    Ok(lf)
+}
+
+/// Eagerly reads a file, for formats and options the lazy scanners don't
+/// cover. `n_rows` limits CSV reads; the other formats are always read whole.
+fn read_file(path: &str, format: FileFormat, source: &Source, n_rows: Option<usize>) -> Result<DataFrame> {
+    let result = match format {
+        FileFormat::Csv => return read_csv(path, &source.options, text_columns(source), n_rows),
+        FileFormat::Parquet => ParquetReader::new(open(path)?).finish(),
+        FileFormat::Ndjson => JsonLineReader::new(open(path)?).finish(),
+        FileFormat::Json => JsonReader::new(open(path)?)
+            .with_json_format(JsonFormat::Json)
+            .finish(),
+        FileFormat::Ipc => IpcReader::new(open(path)?).finish(),
+    };
+    result.map_err(|e| anyhow::anyhow!("Failed to read '{}': {}", path, e))
+}
+
+fn read_csv(path: &str, options: &CsvOptions, dtypes: Schema, n_rows: Option<usize>) -> Result<DataFrame> {
+    let dtypes = (!dtypes.is_empty()).then(|| Arc::new(dtypes));
+    match options.encoding {
+        // Polars only decodes UTF-8, so Latin-1 files are transcoded up front.
+        Encoding::Latin1 => {
+            let mut bytes = Vec::new();
+            open(path)?.read_to_end(&mut bytes)?;
+            let text: String = bytes.iter().map(|&b| b as char).collect();
+            configure_csv(CsvReader::new(Cursor::new(text.into_bytes())), options)
+                .with_dtypes(dtypes)
+                .with_n_rows(n_rows)
+                .finish()
+        }
+        _ => configure_csv(CsvReader::new(open(path)?), options)
+            .with_dtypes(dtypes)
+            .with_n_rows(n_rows)
+            .finish(),
+    }
+    .map_err(|e| anyhow::anyhow!("Failed to read '{}': {}", path, e))
+}
+
+/// Opens a file, or standard input for `-`.
+fn open(path: &str) -> Result<Box<dyn MmapBytesReader>> {
+    if path == STDIN_PATH {
+        return Ok(Box::new(Cursor::new(stdin_bytes()?)));
+    }
+    File::open(path)
+        .map(|file| Box::new(file) as Box<dyn MmapBytesReader>)
+        .map_err(|e| anyhow::anyhow!("Failed to read '{}': {}", path, e))
+}
+
+/// Everything on standard input. It can only be read once, so it is kept for
+/// later reads of the same query (schema analysis, then execution).
+fn stdin_bytes() -> Result<&'static [u8]> {
+    static STDIN: OnceLock<Vec<u8>> = OnceLock::new();
+    if let Some(bytes) = STDIN.get() {
+        return Ok(bytes);
+    }
+    let mut bytes = Vec::new();
+    std::io::stdin().lock().read_to_end(&mut bytes)?;
+    Ok(STDIN.get_or_init(|| bytes))
+}
+
+fn configure_csv<'a, R: MmapBytesReader + 'a>(reader: CsvReader<'a, R>, options: &'a CsvOptions) -> CsvReader<'a, R> {
+    reader
+        .infer_schema(Some(INFER_SCHEMA_ROWS))
+        .has_header(options.header)
+        .with_separator(options.delimiter)
+        .with_quote_char(options.quote)
+        .with_null_values(null_values(options))
+        .with_encoding(csv_encoding(options))
+        .with_skip_rows(options.skip_rows)
+        .with_comment_prefix(options.comment.as_deref())
+}
+
+fn null_values(options: &CsvOptions) -> Option<NullValues> {
+    match options.null_values.as_slice() {
+        [] => None,
+        [single] => Some(NullValues::AllColumnsSingle(single.clone())),
+        many => Some(NullValues::AllColumns(many.to_vec())),
+    }
+}
+
+fn csv_encoding(options: &CsvOptions) -> CsvEncoding {
+    match options.encoding {
+        Encoding::LossyUtf8 => CsvEncoding::LossyUtf8,
+        _ => CsvEncoding::Utf8,
+    }
+}
+
+/// Expands glob patterns (in order of appearance, matches sorted by name).
+pub fn expand_paths(paths: &[String]) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if !path.contains(['*', '?', '[']) {
+            files.push(path.clone());
+            continue;
+        }
+        let mut matches = glob::glob(path)?
+            .map(|entry| entry.map(|p| p.to_string_lossy().into_owned()))
+            .collect::<Result<Vec<_>, _>>()?;
+        if matches.is_empty() {
+            return Err(anyhow::anyhow!("No files match source pattern '{}'", path));
+        }
+        matches.sort();
+        files.extend(matches);
+    }
+    Ok(files)
+}
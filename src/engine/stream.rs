@@ -0,0 +1,61 @@
+use crate::ast::{FileFormat, Query, Source, WriteMode};
+use crate::sink::Sink;
+use super::{plan, LoadedSources};
+use polars::prelude::*;
+use anyhow::Result;
+use std::path::Path;
+
+/// Sources larger than this (in bytes, summed over all files) run on the
+/// streaming engine even without `--streaming`.
+pub const STREAMING_THRESHOLD_BYTES: u64 = 1 << 30;
+
+/// Total on-disk size of the files a source reads.
+pub fn source_size(source: &Source) -> Result<u64> {
+    let mut total = 0;
+    for path in super::source::expand_paths(&source.paths)? {
+        total += std::fs::metadata(&path)?.len();
+    }
+    Ok(total)
+}
+
+/// Whether a query should use the streaming engine when not explicitly requested.
+pub fn should_stream(source: &Source) -> bool {
+    source_size(source).is_ok_and(|size| size > STREAMING_THRESHOLD_BYTES)
+}
+
+/// Runs a query on Polars' streaming engine, processing the input in batches.
+pub fn execute_query_streaming(query: Query) -> Result<DataFrame> {
+    Ok(plan(query, true, &LoadedSources::new())?.with_streaming(true).collect()?)
+}
+
+/// Streams a query's result straight into a file without materialising it.
+/// Queries that can't be sunk run on the streaming engine, are collected and
+/// then written: appends, queries with a stage that materialises its input
+/// (`sample`, `cluster`, `pivot`) and plans Polars can't sink as a whole (e.g.
+/// over a source read eagerly). Any other failure is returned.
+pub fn sink_query(query: Query, path: &Path, sink: &Sink) -> Result<()> {
+    if sink.format == FileFormat::Json {
+        return Err(anyhow::anyhow!("JSON arrays can't be streamed; write NDJSON instead"));
+    }
+    let sinkable = sink.mode != WriteMode::Append
+        && query.sample.is_none()
+        && query.cluster.is_none()
+        && query.pivot.is_none();
+    let lf = plan(query, true, &LoadedSources::new())?.with_streaming(true);
+    if sinkable {
+        match sink.stream(lf.clone(), path) {
+            Ok(()) => return Ok(()),
+            Err(e) if !runs_outside_streaming(&e) => return Err(e),
+            Err(_) => {}
+        }
+    }
+    sink.write_file(&mut lf.collect()?, path)
+}
+
+/// Whether Polars refused to sink because part of the plan needs the standard engine.
+fn runs_outside_streaming(error: &anyhow::Error) -> bool {
+    matches!(
+        error.downcast_ref::<PolarsError>(),
+        Some(PolarsError::InvalidOperation(message)) if message.contains("not yet supported in standard engine")
+    )
+}
@@ -1,3 +1,4 @@
+use crate::ast::Span;
 use thiserror::Error;
 
 /// Error types for parsing `.query` files.
@@ -8,4 +9,72 @@ pub enum QueryError {
 
     #[error("Syntax error: {0}")]
     Expected(String),
+
+    /// Text that isn't part of any token, e.g. `@`, or the `-` of `-3`.
+    #[error("Syntax error: Unrecognised text '{text}'")]
+    Lexical { text: String, span: Span },
+
+    /// The query parsed but doesn't fit the data, e.g. an unknown column or a type mismatch.
+    #[error("Semantic error: {message}")]
+    Semantic { message: String, span: Option<Span> },
+}
+
+impl QueryError {
+    /// Formats the error for display, pointing at the offending part of `input` when known:
+    ///
+    /// ```text
+    /// Semantic error at line 3, column 8: Unknown column 'agee'
+    ///   filter agee > 30
+    ///          ^^^^
+    /// ```
+    pub fn render(&self, input: &str) -> String {
+        let (kind, message, span) = match self {
+            QueryError::Semantic { message, span: Some(span) } => ("Semantic error", message.clone(), span),
+            QueryError::Lexical { text, span } => ("Syntax error", format!("Unrecognised text '{}'", text), span),
+            _ => return self.to_string(),
+        };
+        let line_start = input[..span.start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = input[span.start..].find('\n').map_or(input.len(), |i| span.start + i);
+        let line_no = input[..span.start].matches('\n').count() + 1;
+        let column = input[line_start..span.start].chars().count() + 1;
+        let width = input[span.start..span.end.min(line_end)].chars().count().max(1);
+        format!(
+            "{} at line {}, column {}: {}\n  {}\n  {}{}",
+            kind,
+            line_no,
+            column,
+            message,
+            &input[line_start..line_end],
+            " ".repeat(column - 1),
+            "^".repeat(width)
+        )
+    }
+}
+
+/// The candidate closest to `name` by edit distance (ignoring case), if it is
+/// close enough to be a likely typo: at most one edit per three characters.
+pub fn did_you_mean<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let name = name.to_lowercase();
+    let max_distance = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(&name, &candidate.to_lowercase()), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Levenshtein distance between two strings, counted in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
 }
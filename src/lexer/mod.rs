@@ -1,4 +1,6 @@
 use logos::Logos;
+use crate::ast::Span;
+use crate::errors::QueryError;
 
 /// Tokens for the next-gen CSV query language.
 #[derive(Logos, Debug, PartialEq, Clone)]
@@ -36,6 +38,24 @@ pub enum Token {
     Watch,
     #[token("on_error")]
     OnError,
+    #[token("sample")]
+    Sample,
+    #[token("seed")]
+    Seed,
+    #[token("into")]
+    Into,
+    #[token("schema")]
+    Schema,
+    #[token("pivot")]
+    Pivot,
+    #[token("unpivot")]
+    Unpivot,
+    #[token("as")]
+    As,
+    #[token("explain")]
+    Explain,
+    #[token("write")]
+    Write,
     #[token("true")]
     True,
     #[token("false")]
@@ -65,6 +85,8 @@ pub enum Token {
     RParen,
     #[token("|>")]
     Pipe,
+    #[token("%")]
+    Percent,
 
     // Literals
     #[regex(r#""([^"\\]|\\.)*""#, |lex| lex.slice().trim_matches('"').to_string())]
@@ -76,18 +98,59 @@ pub enum Token {
     #[regex(r"[0-9]+", |lex| lex.slice().parse().unwrap_or(0))]
     Number(i64),
 
+    /// A name, or any text between backticks (`` `sample` ``, `` `first name` ``)
+    /// for columns that clash with a keyword or aren't plain words.
     #[regex(r"[a-zA-Z_][a-zA-Z0-9_]*", |lex| lex.slice().to_string())]
+    #[regex(r"`[^`\n]+`", |lex| lex.slice().trim_matches('`').to_string())]
     Ident(String),
 
-    // Ignored: whitespace and comments
+    /// `# ...` up to the end of the line. Only the formatter keeps these;
+    /// `tokenize` and `tokenize_spanned` drop them.
+    #[regex(r"#[^\n]*", |lex| lex.slice().trim_end().to_string())]
+    Comment(String),
+
+    // Ignored: whitespace
     #[regex(r"[ \t\n\r\f]+", logos::skip)]
-    #[regex(r"#.*", logos::skip)]
     Whitespace,
 }
 
+/// Spelling of every keyword token, for suggestions on typos.
+pub const KEYWORDS: &[&str] = &[
+    "source", "show", "filter", "sort", "by", "cap", "map", "unique", "merge", "alias", "cluster",
+    "with", "exclude", "fuzzy", "watch", "on_error", "sample", "seed", "into", "schema", "pivot",
+    "unpivot", "as", "explain", "write", "true", "false",
+];
+
 /// Tokenizes the input query text into a list of tokens.
 pub fn tokenize(input: &str) -> Vec<Token> {
     Token::lexer(input)
         .filter_map(Result::ok)
+        .filter(|token| !matches!(token, Token::Comment(_)))
+        .collect()
+}
+
+/// Tokenizes the input and keeps each token's byte range, for error reporting.
+pub fn tokenize_spanned(input: &str) -> Vec<(Token, Span)> {
+    lex(input)
+        .filter_map(|(token, span)| token.ok().map(|token| (token, span)))
+        .filter(|(token, _)| !matches!(token, Token::Comment(_)))
         .collect()
 }
+
+/// Like `tokenize_spanned`, but keeps `Token::Comment`s, for tools that
+/// rewrite the query text. Text the lexer doesn't recognise is an error
+/// rather than being skipped, as such tools would otherwise drop it.
+pub fn tokenize_with_comments(input: &str) -> Result<Vec<(Token, Span)>, QueryError> {
+    lex(input)
+        .map(|(token, span)| match token {
+            Ok(token) => Ok((token, span)),
+            Err(()) => Err(QueryError::Lexical { text: input[span.start..span.end].to_string(), span }),
+        })
+        .collect()
+}
+
+fn lex(input: &str) -> impl Iterator<Item = (Result<Token, ()>, Span)> + '_ {
+    Token::lexer(input)
+        .spanned()
+        .map(|(token, range)| (token, Span { start: range.start, end: range.end }))
+}
@@ -1,11 +1,17 @@
 pub mod lexer;
 pub mod parser;
 pub mod ast;
+pub mod plan;
+pub mod analyzer;
 pub mod engine;
+pub mod sink;
+pub mod printer;
+pub mod repl;
 pub mod errors;
 pub mod cli;
 
-use lexer::tokenize;
+use analyzer::analyze;
+use lexer::tokenize_spanned;
 use parser::Parser;
 use engine::execute_query;
 use errors::QueryError;
@@ -13,28 +19,44 @@ use errors::QueryError;
 pub struct PipelineResult {
     pub tokens: Vec<lexer::Token>,
     pub ast: ast::Query,
+    /// Where the query references each column, for the analyzer's errors.
+    pub column_spans: ast::ColumnSpans,
     pub output: Option<polars::prelude::DataFrame>,
 }
 
-/// Runs the full query pipeline: lexing, parsing, execution.
-/// Returns all intermediate results for debugging or further processing.
+/// A failed pipeline run. The tokens are kept, as they help explain why a
+/// query didn't parse.
+#[derive(Debug, thiserror::Error)]
+#[error("{error}")]
+pub struct PipelineError {
+    pub tokens: Vec<lexer::Token>,
+    pub error: QueryError,
+}
+
+/// Runs the full query pipeline: lexing, parsing, analysis, execution.
+/// Returns all intermediate results for debugging or further processing;
+/// on failure, the tokens are returned with the error.
 pub fn run_pipeline(
     input: &str,
     execute: bool,
-) -> Result<PipelineResult, QueryError> {
-    let tokens = tokenize(input);
+) -> Result<PipelineResult, PipelineError> {
+    let spanned = tokenize_spanned(input);
+    let tokens: Vec<lexer::Token> = spanned.iter().map(|(token, _)| token.clone()).collect();
+    let fail = |error| PipelineError { tokens: tokens.clone(), error };
 
-    let mut parser = Parser::new(tokens.clone());
-    let ast = parser.parse_query()?;
+    let mut parser = Parser::with_spans(spanned);
+    let ast = parser.parse_query().map_err(fail)?;
+    let column_spans = parser.column_spans().clone();
 
     let output = if execute {
+        analyze(&ast, &column_spans).map_err(fail)?;
         Some(
             execute_query(ast.clone())
-                .map_err(|e| QueryError::Unexpected(e.to_string()))?
+                .map_err(|e| fail(QueryError::Unexpected(e.to_string())))?
         )
     } else {
         None
     };
 
-    Ok(PipelineResult { tokens, ast, output })
+    Ok(PipelineResult { tokens, ast, column_spans, output })
 }
@@ -1,117 +1,305 @@
-mod lexer;
-mod parser;
-mod ast;
-mod engine;
-mod errors;
-
 use clap::Parser as ClapParser;
 use std::fs;
-use std::path::PathBuf;
-use crate::lexer::tokenize;
-use crate::parser::Parser;
-use crate::engine::execute_query;
+use std::io;
+use std::path::{Path, PathBuf};
+use query_compiler::analyzer::analyze;
+use query_compiler::ast::{ColumnSpans, FileFormat, Query, Source, WriteMode, WriteTo, STDIN_PATH};
+use query_compiler::cli::{
+    CheckArgs, CliArgs, Command, DebugArgs, ExplainArgs, FmtArgs, HeadArgs, PlanFormat, QueryArgs, RunArgs, SchemaArgs,
+    TableArgs,
+};
+use query_compiler::plan::{lower, optimize, to_dot, to_mermaid, GraphNode};
+use query_compiler::engine::{
+    execute_query, explain, execute_query_streaming, should_stream, sink_query, source_schema,
+};
+use query_compiler::printer::format_query;
+use query_compiler::run_pipeline;
+use query_compiler::sink::{Compression, CsvSinkOptions, QuoteStyle, Sink, Table, TableFormat};
+
+fn main() -> anyhow::Result<()> {
+    let args = CliArgs::parse();
+    match args.command {
+        None => run(&args.run),
+        Some(Command::Run(run_args)) => run(&run_args),
+        Some(Command::Check(check_args)) => check(&check_args),
+        Some(Command::Fmt(fmt_args)) => fmt(&fmt_args),
+        Some(Command::Explain(explain_args)) => explain_query(&explain_args),
+        Some(Command::Schema(schema_args)) => schema(&schema_args),
+        Some(Command::Head(head_args)) => head(&head_args),
+        Some(Command::Repl) => query_compiler::repl::run(),
+    }
+}
+
+fn run(args: &RunArgs) -> anyhow::Result<()> {
+    let (query_str, ast, spans) = compile(&args.query, &args.debug)?;
+    analyze(&ast, &spans).map_err(|e| anyhow::anyhow!(e.render(&query_str)))?;
+
+    if ast.explain {
+        println!("\n# Explain:");
+        print!("{}", explain(ast)?);
+        return Ok(());
+    }
 
-/// CLI arguments for the CSV Query Next-Gen.
-#[derive(ClapParser, Debug)]
-#[command(name = "csv-query", about = "Compile and execute next-gen queries on CSV data.")]
-pub struct CliArgs {
-    #[arg(short, long)]
-    pub query: PathBuf,
+    // Resolve the output format before running so a bad one fails without side effects.
+    let output = resolve_output(args, ast.write.as_ref())?;
+    let streaming = args.streaming || should_stream(&ast.source);
 
-    #[arg(short, long)]
-    pub output: Option<PathBuf>,
+    // With nothing to print, a streamed result goes straight to the output file.
+    if streaming
+        && !args.show
+        && let Some((path, Output::Data(sink))) = &output
+        && path != Path::new("-")
+        && sink.format != FileFormat::Json
+    {
+        sink_query(ast, path, sink)?;
+        println!("Exported result to {}", path.display());
+        return Ok(());
+    }
+
+    let mut result_df = if streaming {
+        execute_query_streaming(ast.clone())?
+    } else {
+        execute_query(ast.clone())?
+    };
+    if args.show {
+        println!("\n# Result DataFrame:");
+        print!("{}", table(&args.table, TableFormat::Table).render(&result_df));
+    }
+
+    if let Some((path, output)) = output {
+        let stdout = path == Path::new("-");
+        match output {
+            Output::Data(sink) if stdout => sink.write(&mut result_df, io::stdout().lock())?,
+            Output::Text(table) if stdout => table.write(&result_df, io::stdout().lock())?,
+            Output::Data(sink) => {
+                sink.write_file(&mut result_df, &path)?;
+                println!("Exported result to {}", path.display());
+            }
+            Output::Text(table) => {
+                table.write_file(&result_df, &path)?;
+                println!("Exported result to {}", path.display());
+            }
+            Output::Partitioned(sink, columns) => {
+                let files = sink.write_partitioned(&result_df, &path, &columns)?;
+                println!("Exported {} partitions to {}", files.len(), path.display());
+            }
+        }
+    }
 
-    #[arg(long, default_value_t = false)]
-    pub tokens: bool,
+    Ok(())
+}
 
-    #[arg(long, default_value_t = false)]
-    pub ast: bool,
+/// Parses and analyzes the query without running it. Learning the sources'
+/// columns still reads the first rows of CSV files, and JSON arrays and stdin whole.
+fn check(args: &CheckArgs) -> anyhow::Result<()> {
+    let (query_str, ast, spans) = compile(&args.query, &args.debug)?;
+    analyze(&ast, &spans).map_err(|e| anyhow::anyhow!(e.render(&query_str)))?;
+    println!("OK");
+    Ok(())
+}
 
-    #[arg(long, default_value_t = false)]
-    pub show: bool,
+/// Formats each file in place, or with `--check` lists the ones that aren't formatted.
+fn fmt(args: &FmtArgs) -> anyhow::Result<()> {
+    let stdin = vec![PathBuf::from("-")];
+    let files = if args.files.is_empty() { &stdin } else { &args.files };
+    let mut unformatted = 0;
+    for path in files {
+        let text = if path == Path::new("-") { io::read_to_string(io::stdin())? } else { fs::read_to_string(path)? };
+        let formatted = format_query(&text)
+            .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e.render(&text)))?;
+        if args.check {
+            if formatted != text {
+                eprintln!("{} is not formatted", path.display());
+                unformatted += 1;
+            }
+        } else if path == Path::new("-") {
+            print!("{}", formatted);
+        } else if formatted != text {
+            fs::write(path, formatted)?;
+            println!("Formatted {}", path.display());
+        }
+    }
+    if unformatted > 0 {
+        return Err(anyhow::anyhow!("{} of {} queries need formatting", unformatted, files.len()));
+    }
+    Ok(())
 }
 
-fn main() -> anyhow::Result<()> {
-    let args = CliArgs::parse();
+fn explain_query(args: &ExplainArgs) -> anyhow::Result<()> {
+    let (query_str, ast, spans) = compile(&args.query, &DebugArgs::default())?;
+    if let Some(format) = args.plan_format {
+        let graph = if args.ast {
+            GraphNode::from(&ast)
+        } else {
+            GraphNode::from(&optimize(lower(ast)?))
+        };
+        match format {
+            PlanFormat::Dot => print!("{}", to_dot(&graph)),
+            PlanFormat::Mermaid => print!("{}", to_mermaid(&graph)),
+        }
+        return Ok(());
+    }
+    analyze(&ast, &spans).map_err(|e| anyhow::anyhow!(e.render(&query_str)))?;
+    print!("{}", explain(ast)?);
+    Ok(())
+}
 
-    let query_str = fs::read_to_string(&args.query)?;
+fn schema(args: &SchemaArgs) -> anyhow::Result<()> {
+    let schema = source_schema(&Source::from(args.file.as_str()))?;
+    let width = schema.iter_names().map(|name| name.chars().count()).max().unwrap_or(0);
+    for (name, dtype) in schema.iter() {
+        println!("{:width$}  {}", name, dtype, width = width);
+    }
+    Ok(())
+}
 
-    let tokens = tokenize(&query_str);
-    if args.tokens {
+fn head(args: &HeadArgs) -> anyhow::Result<()> {
+    let query = Query { source: Source::from(args.file.as_str()), cap: Some(args.rows), ..Default::default() };
+    let df = execute_query(query)?;
+    let mut table = Table::new(TableFormat::Table);
+    table.max_width = args.max_width.or(table.max_width);
+    table.write(&df, io::stdout().lock())
+}
+
+/// Reads, lexes and parses the query, printing the tokens and AST when asked.
+fn compile(args: &QueryArgs, debug: &DebugArgs) -> anyhow::Result<(String, Query, ColumnSpans)> {
+    let query_str = read_query(args)?;
+
+    let result = run_pipeline(&query_str, false);
+    // Tokens are printed even when parsing fails, as they help debug the query.
+    if debug.tokens {
+        let tokens = match &result {
+            Ok(result) => &result.tokens,
+            Err(failure) => &failure.tokens,
+        };
         println!("# Tokens:");
-        for t in &tokens {
+        for t in tokens {
             println!("{:?}", t);
         }
     }
 
-    let mut parser = Parser::new(tokens.clone());
-    let ast = parser.parse_query()?;
-    if args.ast {
+    let result = result.map_err(|e| anyhow::anyhow!(e.error.render(&query_str)))?;
+    let ast = result.ast;
+    if args.query.as_deref() == Some(Path::new("-")) && ast.source.paths.iter().any(|p| p == STDIN_PATH) {
+        return Err(anyhow::anyhow!("stdin can't supply both the query and its data; pass the query with -e"));
+    }
+    if debug.ast {
         println!("\n# AST:");
         println!("{:#?}", ast);
     }
+    Ok((query_str, ast, result.column_spans))
+}
 
-    let result_df = execute_query(ast.clone())?;
-    if args.show {
-        println!("\n# Result DataFrame:");
-        println!("{}", &result_df);
+/// The query text from `-e`, stdin (`--query -`) or the query file.
+fn read_query(args: &QueryArgs) -> anyhow::Result<String> {
+    match (&args.eval, &args.query) {
+        (Some(text), _) => Ok(text.clone()),
+        (None, Some(path)) if path == Path::new("-") => Ok(io::read_to_string(io::stdin())?),
+        (None, Some(path)) => Ok(fs::read_to_string(path)?),
+        (None, None) => Err(anyhow::anyhow!("Pass a query file with --query or query text with -e")),
     }
+}
 
-    if let Some(out_path) = &args.output {
-        let ext = out_path.extension().and_then(|x| x.to_str()).unwrap_or("csv").to_lowercase();
-        let file_name = out_path.file_name().and_then(|x| x.to_str()).unwrap_or("result");
-        let (dir, full_path) = match ext.as_str() {
-            "csv" => ("reports/csv", format!("reports/csv/{}", file_name)),
-            "json" => ("reports/json", format!("reports/json/{}", file_name)),
-            _ => ("reports", format!("reports/{}", file_name)),
-        };
-        std::fs::create_dir_all(dir)?;
-        let mut file = std::fs::File::create(&full_path)?;
-    
-        match ext.as_str() {
-            "csv" => {
-                use polars::prelude::*;
-                let mut writer = CsvWriter::new(&mut file);
-                writer.finish(&mut result_df.clone())?;
-                println!("Exported result to {}", full_path);
-            }
-            "json" => {
-                use std::io::Write;
-                use serde_json::json;
-                let columns = result_df.get_columns();
-                let headers: Vec<&str> = result_df.get_column_names();
-                let mut records = vec![];
-    
-                for i in 0..result_df.height() {
-                    let mut record = serde_json::Map::new();
-                    for (col, name) in columns.iter().zip(headers.iter()) {
-                        let value = col.get(i);
-                        let json_value = match value {
-                            Ok(polars::prelude::AnyValue::String(s)) => json!(s),
-                            Ok(polars::prelude::AnyValue::Int64(n)) => json!(n),
-                            Ok(polars::prelude::AnyValue::UInt64(n)) => json!(n),
-                            Ok(polars::prelude::AnyValue::Float64(f)) => json!(f),
-                            Ok(polars::prelude::AnyValue::Float32(f)) => json!(f),
-                            Ok(polars::prelude::AnyValue::Int32(n)) => json!(n),
-                            Ok(polars::prelude::AnyValue::Boolean(b)) => json!(b),
-                            Ok(ref v) => json!(v.to_string()),
-                            Err(_) => json!(null),
-                        };
-                        record.insert((*name).to_string(), json_value);
-                    }
-                    records.push(serde_json::Value::Object(record));
-                }
-    
-                let json = serde_json::to_string_pretty(&records)?;
-                file.write_all(json.as_bytes())?;
-                println!("Exported result to {}", full_path);
-            }
-            _ => {
-                eprintln!("Unsupported output format: {}", ext);
-            }
+/// How the result is written out.
+enum Output {
+    /// A data file format.
+    Data(Sink),
+    /// A human-readable table.
+    Text(Table),
+    /// One data file per group of the given columns, under a directory.
+    Partitioned(Sink, Vec<String>),
+}
+
+/// Where and how to write the result. Command-line options take precedence over
+/// the query's `write to` clause; `--format` alone writes to stdout.
+fn resolve_output(args: &RunArgs, write: Option<&WriteTo>) -> anyhow::Result<Option<(PathBuf, Output)>> {
+    let path = match (&args.output, write, &args.format) {
+        (Some(path), _, _) => path.clone(),
+        (None, Some(write), _) => PathBuf::from(&write.path),
+        (None, None, Some(_)) => PathBuf::from("-"),
+        (None, None, None) => return Ok(None),
+    };
+    let partition_by = match (&args.partition_by, write) {
+        (columns, _) if !columns.is_empty() => columns.clone(),
+        (_, Some(write)) => write.partition_by.clone(),
+        _ => Vec::new(),
+    };
+    let name = args
+        .format
+        .as_deref()
+        .or_else(|| write.and_then(|w| w.format).map(FileFormat::name))
+        .or_else(|| path.extension().and_then(|ext| ext.to_str()));
+    if let Some(format) = name.and_then(TableFormat::from_name) {
+        if !partition_by.is_empty() {
+            return Err(anyhow::anyhow!("Partitioned output needs a data format, not '{}'", name.unwrap_or_default()));
         }
+        return Ok(Some((path, Output::Text(table(&args.table, format)))));
+    }
+    let mode = match &args.mode {
+        Some(name) => WriteMode::from_name(name).ok_or_else(|| {
+            anyhow::anyhow!("Unknown write mode '{}' (expected overwrite, append or error_if_exists)", name)
+        })?,
+        None => write.map(|w| w.mode).unwrap_or_default(),
+    };
+    let sink = output_sink(args, output_format(name)?, mode)?;
+    if partition_by.is_empty() {
+        return Ok(Some((path, Output::Data(sink))));
+    }
+    if path == Path::new("-") {
+        return Err(anyhow::anyhow!("Partitioned output needs a directory, not stdout"));
     }
-    
+    Ok(Some((path, Output::Partitioned(sink, partition_by))))
+}
 
-    Ok(())
+/// The table renderer for `format`, configured from `--rows`, `--max-width` and `--full`.
+fn table(args: &TableArgs, format: TableFormat) -> Table {
+    let mut table = Table::new(format);
+    if args.full {
+        table = table.full();
+    }
+    if let Some(rows) = args.rows {
+        table.max_rows = Some(rows);
+    }
+    if let Some(width) = args.max_width {
+        table.max_width = Some(width);
+    }
+    table
+}
+
+/// The data format named by `--format` or the output file's extension (CSV when there is none).
+fn output_format(name: Option<&str>) -> anyhow::Result<FileFormat> {
+    let Some(name) = name else {
+        return Ok(FileFormat::Csv);
+    };
+    FileFormat::from_name(name).ok_or_else(|| {
+        anyhow::anyhow!(
+            "Unsupported output format '{}' (expected csv, json, ndjson, parquet, ipc, markdown, html or table)",
+            name
+        )
+    })
+}
+
+/// The sink for `format` and `mode`, configured from the CSV and compression flags.
+fn output_sink(args: &RunArgs, format: FileFormat, mode: WriteMode) -> anyhow::Result<Sink> {
+    let mut csv = CsvSinkOptions { bom: args.bom, ..Default::default() };
+    if let Some(delimiter) = args.delimiter {
+        csv.delimiter = u8::try_from(delimiter)
+            .ok()
+            .filter(u8::is_ascii)
+            .ok_or_else(|| anyhow::anyhow!("The delimiter must be a single ASCII character, got '{}'", delimiter))?;
+    }
+    if let Some(name) = &args.quote_style {
+        csv.quote_style = QuoteStyle::from_name(name).ok_or_else(|| {
+            anyhow::anyhow!("Unknown quote style '{}' (expected necessary, always, non-numeric or never)", name)
+        })?;
+    }
+    let mut sink = Sink::new(format).with_csv(csv).with_mode(mode);
+    if let Some(name) = &args.compression {
+        let compression = Compression::from_name(name).ok_or_else(|| {
+            anyhow::anyhow!("Unknown compression '{}' (expected uncompressed, snappy, gzip, lz4 or zstd)", name)
+        })?;
+        sink = sink.with_compression(compression);
+    }
+    sink.check()?;
+    Ok(sink)
 }
@@ -1,25 +1,40 @@
 use crate::lexer::Token;
-use crate::ast::{Query, Expr, Value, Sort, SortDirection};
-use crate::errors::QueryError;
+use crate::ast::{STDIN_PATH, Query, ColumnSpans, Span, Source, SourceSchema, ColumnDef, ColumnType, Encoding, FileFormat, Expr, Value, Sort, SortDirection, Sample, SampleSize, Cluster, Pivot, Unpivot, AggFunc, WriteTo, WriteMode};
+use crate::errors::{did_you_mean, QueryError};
+use crate::lexer::KEYWORDS;
 
 #[derive(Debug)]
 pub struct Parser {
     tokens: Vec<Token>,
     position: usize,
+    /// Byte range of each token; empty when the parser was built without spans.
+    spans: Vec<Span>,
+    column_spans: ColumnSpans,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, position: 0 }
+        Self { tokens, position: 0, spans: Vec::new(), column_spans: ColumnSpans::default() }
+    }
+
+    /// Builds a parser that records where columns are referenced, so later
+    /// errors can point at the query text.
+    pub fn with_spans(tokens: Vec<(Token, Span)>) -> Self {
+        let (tokens, spans) = tokens.into_iter().unzip();
+        Self { tokens, position: 0, spans, column_spans: ColumnSpans::default() }
+    }
+
+    /// Where the columns of the parsed query were referenced; empty when the
+    /// parser was built without spans.
+    pub fn column_spans(&self) -> &ColumnSpans {
+        &self.column_spans
     }
 
     /// Parses the entire query and returns an AST node.
     pub fn parse_query(&mut self) -> Result<Query, QueryError> {
+        let explain = self.match_token(&Token::Explain);
         self.expect_token(&Token::Source)?;
-        let source = match self.next() {
-            Some(Token::StringLiteral(s)) => s,
-            _ => return Err(QueryError::Expected("Expected string after 'source'".into())),
-        };
+        let source = self.parse_source()?;
 
         let mut filter = None;
         let mut show = Vec::new();
@@ -27,6 +42,11 @@ impl Parser {
         let mut cap = None;
         let mut map = None;
         let mut unique = false;
+        let mut sample = None;
+        let mut cluster = None;
+        let mut pivot = None;
+        let mut unpivot = None;
+        let mut write = None;
 
         // The order of keywords is flexible: filter, show, sort, cap, map, unique...
         while let Some(token) = self.peek() {
@@ -56,6 +76,27 @@ impl Parser {
                     self.next();
                     unique = true;
                 }
+                Token::Sample => {
+                    self.next();
+                    sample = Some(self.parse_sample()?);
+                }
+                Token::Cluster => {
+                    self.next();
+                    self.expect_token(&Token::By)?;
+                    cluster = Some(self.parse_cluster()?);
+                }
+                Token::Pivot => {
+                    self.next();
+                    pivot = Some(self.parse_pivot()?);
+                }
+                Token::Unpivot => {
+                    self.next();
+                    unpivot = Some(self.parse_unpivot()?);
+                }
+                Token::Write => {
+                    self.next();
+                    write = Some(self.parse_write()?);
+                }
                 Token::Pipe => {
                     self.next();
                     continue; 
@@ -64,6 +105,17 @@ impl Parser {
             }
         }
 
+        // Anything left over is not a clause, most often a misspelled keyword.
+        if let Some(token) = self.peek() {
+            return Err(match token {
+                Token::Ident(word) => match did_you_mean(word, KEYWORDS.iter().copied()) {
+                    Some(keyword) => QueryError::Expected(format!("Unknown clause '{}'; did you mean '{}'?", word, keyword)),
+                    None => QueryError::Expected(format!("Unknown clause '{}'", word)),
+                },
+                other => QueryError::Unexpected(format!("Unexpected token {:?} after query", other)),
+            });
+        }
+
         Ok(Query {
             source,
             filter,
@@ -72,14 +124,158 @@ impl Parser {
             cap,
             map,
             unique,
+            sample,
+            cluster,
+            pivot,
+            unpivot,
+            write,
+            explain,
         })
     }
 
+    fn parse_source(&mut self) -> Result<Source, QueryError> {
+        // source "a.csv"[, "b.csv"...] or source stdin, followed, in any order, by:
+        //   as format | with option | with (option, ...) | schema [exact] (name type, ...)
+        let mut paths = Vec::new();
+        loop {
+            match self.next() {
+                Some(Token::StringLiteral(s)) => paths.push(s),
+                Some(Token::Ident(name)) if name.eq_ignore_ascii_case("stdin") => paths.push(STDIN_PATH.to_string()),
+                _ => return Err(QueryError::Expected("Expected string after 'source'".into())),
+            }
+            if !self.match_token(&Token::Comma) {
+                break;
+            }
+        }
+
+        let mut source = Source { paths, ..Default::default() };
+        loop {
+            match self.peek() {
+                Some(Token::As) => {
+                    self.next();
+                    source.format = Some(self.parse_format()?);
+                }
+                Some(Token::With) => {
+                    self.next();
+                    if self.match_token(&Token::LParen) {
+                        loop {
+                            self.parse_source_option(&mut source)?;
+                            if !self.match_token(&Token::Comma) {
+                                break;
+                            }
+                        }
+                        self.expect_token(&Token::RParen)?;
+                    } else {
+                        self.parse_source_option(&mut source)?;
+                    }
+                }
+                Some(Token::Schema) => {
+                    self.next();
+                    source.schema = Some(self.parse_schema()?);
+                }
+                _ => break,
+            }
+        }
+        Ok(source)
+    }
+
+    fn parse_format(&mut self) -> Result<FileFormat, QueryError> {
+        match self.next() {
+            Some(Token::Ident(name)) => FileFormat::from_name(&name)
+                .ok_or_else(|| QueryError::Expected(format!("Unknown source format '{}'", name))),
+            _ => Err(QueryError::Expected("Expected format name after 'as'".into())),
+        }
+    }
+
+    fn parse_schema(&mut self) -> Result<SourceSchema, QueryError> {
+        let exact = self.match_ident("exact");
+        self.expect_token(&Token::LParen)?;
+        let mut columns = Vec::new();
+        loop {
+            let name = match self.next() {
+                Some(Token::Ident(name)) => name,
+                Some(Token::StringLiteral(name)) => name,
+                _ => return Err(QueryError::Expected("Expected column name in schema".into())),
+            };
+            let ty = match self.next() {
+                Some(Token::Ident(ty)) => ColumnType::from_name(&ty)
+                    .ok_or_else(|| QueryError::Expected(format!("Unknown type '{}' for column '{}'", ty, name)))?,
+                _ => return Err(QueryError::Expected(format!("Expected type after column '{}'", name))),
+            };
+            columns.push(ColumnDef { name, ty });
+            if !self.match_token(&Token::Comma) {
+                break;
+            }
+        }
+        self.expect_token(&Token::RParen)?;
+        Ok(SourceSchema { columns, exact })
+    }
+
+    fn parse_source_option(&mut self, source: &mut Source) -> Result<(), QueryError> {
+        let key = match self.next() {
+            Some(Token::Ident(name)) => name,
+            _ => return Err(QueryError::Expected("Expected option name after 'with'".into())),
+        };
+        let options = &mut source.options;
+        match key.as_str() {
+            "source_file" => source.tag_file = self.parse_flag(),
+            "header" => options.header = self.parse_flag(),
+            "delimiter" => options.delimiter = single_byte(&key, &self.parse_string_option(&key)?)?,
+            "quote" => {
+                let quote = self.parse_string_option(&key)?;
+                options.quote = match quote.as_str() {
+                    "" => None,
+                    _ => Some(single_byte(&key, &quote)?),
+                };
+            }
+            "null" => options.null_values.push(self.parse_string_option(&key)?),
+            "encoding" => {
+                let name = self.parse_string_option(&key)?;
+                options.encoding = Encoding::from_name(&name)
+                    .ok_or_else(|| QueryError::Expected(format!("Unsupported encoding '{}'", name)))?;
+            }
+            "skip_rows" => {
+                options.skip_rows = match self.next() {
+                    Some(Token::Number(n)) if n >= 0 => n as usize,
+                    _ => return Err(QueryError::Expected("Expected positive integer after 'skip_rows'".into())),
+                };
+            }
+            "comment" => options.comment = Some(self.parse_string_option(&key)?),
+            _ => {
+                let known = ["source_file", "header", "delimiter", "quote", "null", "encoding", "skip_rows", "comment"];
+                return Err(QueryError::Expected(match did_you_mean(&key, known) {
+                    Some(option) => format!("Unknown source option '{}'; did you mean '{}'?", key, option),
+                    None => format!("Unknown source option '{}'", key),
+                }));
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses an optional `true`/`false` after a flag option; a bare flag means `true`.
+    fn parse_flag(&mut self) -> bool {
+        if self.match_token(&Token::False) {
+            return false;
+        }
+        self.match_token(&Token::True);
+        true
+    }
+
+    fn parse_string_option(&mut self, key: &str) -> Result<String, QueryError> {
+        match self.next() {
+            Some(Token::StringLiteral(s)) => Ok(s),
+            _ => Err(QueryError::Expected(format!("Expected string after '{}'", key))),
+        }
+    }
+
     fn parse_show_fields(&mut self) -> Result<Vec<String>, QueryError> {
         let mut fields = Vec::new();
         loop {
             match self.next() {
-                Some(Token::Ident(name)) => fields.push(name),
+                Some(Token::Ident(name)) => {
+                    self.record_column(&name);
+                    fields.push(name);
+                }
                 Some(Token::Comma) => continue,
                 Some(_) => {
                     self.position -= 1;
@@ -96,6 +292,7 @@ impl Parser {
             Some(Token::Ident(name)) => name,
             _ => return Err(QueryError::Expected("Expected field name after 'sort by'".into())),
         };
+        self.record_column(&column);
         let direction = match self.peek() {
             Some(Token::Ident(dir)) if dir.eq_ignore_ascii_case("desc") => {
                 self.next();
@@ -117,6 +314,166 @@ impl Parser {
         }
     }
 
+    fn parse_sample(&mut self) -> Result<Sample, QueryError> {
+        // sample 1000 rows | sample 5% | sample 2.5%, optionally followed by seed N
+        let size = match self.next() {
+            Some(Token::Number(n)) if self.match_token(&Token::Percent) => SampleSize::Percent(n as f64),
+            Some(Token::Float(f)) if self.match_token(&Token::Percent) => SampleSize::Percent(f),
+            Some(Token::Number(n)) if n >= 0 => {
+                self.match_ident("rows");
+                SampleSize::Rows(n as usize)
+            }
+            _ => return Err(QueryError::Expected("Expected row count or percentage after 'sample'".into())),
+        };
+        if let SampleSize::Percent(p) = size
+            && !(0.0..=100.0).contains(&p)
+        {
+            return Err(QueryError::Expected("Sample percentage must be between 0 and 100".into()));
+        }
+
+        let seed = self.parse_seed()?;
+        Ok(Sample { size, seed })
+    }
+
+    fn parse_cluster(&mut self) -> Result<Cluster, QueryError> {
+        // cluster by a, b into K [as alias] [seed N] [with centroids]
+        let columns = self.parse_show_fields()?;
+        if columns.is_empty() {
+            return Err(QueryError::Expected("Expected column names after 'cluster by'".into()));
+        }
+        self.expect_token(&Token::Into)?;
+        let k = match self.next() {
+            Some(Token::Number(n)) if n > 0 => n as usize,
+            _ => return Err(QueryError::Expected("Expected positive cluster count after 'into'".into())),
+        };
+        let alias = if self.match_token(&Token::As) {
+            match self.next() {
+                Some(Token::Ident(name)) => name,
+                _ => return Err(QueryError::Expected("Expected column name after 'as'".into())),
+            }
+        } else {
+            "cluster".to_string()
+        };
+        let seed = self.parse_seed()?;
+        let centroids = if self.match_token(&Token::With) {
+            if !self.match_ident("centroids") {
+                return Err(QueryError::Expected("Expected 'centroids' after 'with'".into()));
+            }
+            true
+        } else {
+            false
+        };
+        Ok(Cluster { columns, k, alias, seed, centroids })
+    }
+
+    fn parse_pivot(&mut self) -> Result<Pivot, QueryError> {
+        // pivot agg(values) by columns for index[, index...]
+        let agg = match self.next() {
+            Some(Token::Ident(name)) => AggFunc::from_name(&name)
+                .ok_or_else(|| QueryError::Expected(format!("Unknown aggregate function '{}'", name)))?,
+            _ => return Err(QueryError::Expected("Expected aggregate function after 'pivot'".into())),
+        };
+        self.expect_token(&Token::LParen)?;
+        let values = match self.next() {
+            Some(Token::Ident(name)) => name,
+            _ => return Err(QueryError::Expected("Expected column name inside aggregate".into())),
+        };
+        self.record_column(&values);
+        self.expect_token(&Token::RParen)?;
+        self.expect_token(&Token::By)?;
+        let columns = match self.next() {
+            Some(Token::Ident(name)) => name,
+            _ => return Err(QueryError::Expected("Expected column name after 'by'".into())),
+        };
+        self.record_column(&columns);
+        if !self.match_ident("for") {
+            return Err(QueryError::Expected("Expected 'for' in pivot clause".into()));
+        }
+        let index = self.parse_show_fields()?;
+        if index.is_empty() {
+            return Err(QueryError::Expected("Expected column names after 'for'".into()));
+        }
+        Ok(Pivot { agg, values, columns, index })
+    }
+
+    fn parse_unpivot(&mut self) -> Result<Unpivot, QueryError> {
+        // unpivot a, b, c into variable, value
+        let columns = self.parse_show_fields()?;
+        if columns.is_empty() {
+            return Err(QueryError::Expected("Expected column names after 'unpivot'".into()));
+        }
+        self.expect_token(&Token::Into)?;
+        let variable = match self.next() {
+            Some(Token::Ident(name)) => name,
+            _ => return Err(QueryError::Expected("Expected variable column name after 'into'".into())),
+        };
+        self.expect_token(&Token::Comma)?;
+        let value = match self.next() {
+            Some(Token::Ident(name)) => name,
+            _ => return Err(QueryError::Expected("Expected value column name after ','".into())),
+        };
+        Ok(Unpivot { columns, variable, value })
+    }
+
+    fn parse_write(&mut self) -> Result<WriteTo, QueryError> {
+        // write to "path" [format F] [mode M] [partition by a, b], options in any order
+        if !self.match_ident("to") {
+            return Err(QueryError::Expected("Expected 'to' after 'write'".into()));
+        }
+        let path = match self.next() {
+            Some(Token::StringLiteral(path)) => path,
+            _ => return Err(QueryError::Expected("Expected output path after 'write to'".into())),
+        };
+        let mut write = WriteTo { path, ..Default::default() };
+        loop {
+            if self.match_ident("format") {
+                write.format = Some(match self.next() {
+                    Some(Token::Ident(name)) => FileFormat::from_name(&name)
+                        .ok_or_else(|| QueryError::Expected(format!("Unknown output format '{}'", name)))?,
+                    _ => return Err(QueryError::Expected("Expected format name after 'format'".into())),
+                });
+            } else if self.match_ident("mode") {
+                write.mode = match self.next() {
+                    Some(Token::Ident(name)) => WriteMode::from_name(&name).ok_or_else(|| {
+                        QueryError::Expected(format!(
+                            "Unknown write mode '{}' (expected overwrite, append or error_if_exists)",
+                            name
+                        ))
+                    })?,
+                    _ => return Err(QueryError::Expected("Expected write mode after 'mode'".into())),
+                };
+            } else if self.match_ident("partition") {
+                self.expect_token(&Token::By)?;
+                // Commas are required here, since options may follow the column names.
+                loop {
+                    match self.next() {
+                        Some(Token::Ident(name)) => {
+                            self.record_column(&name);
+                            write.partition_by.push(name);
+                        }
+                        _ => return Err(QueryError::Expected("Expected column name in 'partition by'".into())),
+                    }
+                    if !self.match_token(&Token::Comma) {
+                        break;
+                    }
+                }
+            } else {
+                return Ok(write);
+            }
+        }
+    }
+
+    /// Parses an optional `seed N` suffix.
+    fn parse_seed(&mut self) -> Result<Option<u64>, QueryError> {
+        if !self.match_token(&Token::Seed) {
+            return Ok(None);
+        }
+        match self.next() {
+            Some(Token::Number(n)) if n >= 0 => Ok(Some(n as u64)),
+            _ => Err(QueryError::Expected("Expected positive integer after 'seed'".into())),
+        }
+    }
+
     fn parse_map(&mut self) -> Result<(String, Expr), QueryError> {
         // Simple map: map field = expr
         let field = match self.next() {
@@ -162,6 +519,7 @@ impl Parser {
             Some(Token::Ident(name)) => name,
             _ => return Err(QueryError::Expected("Expected field name in expression".into())),
         };
+        self.record_column(&field);
 
         let op = match self.next() {
             Some(Token::Eq) => "==",
@@ -198,22 +556,29 @@ impl Parser {
 
     /// Checks for an identifier (e.g., 'and', 'or') and consumes it if found.
     fn match_ident(&mut self, expected: &str) -> bool {
-        if let Some(Token::Ident(name)) = self.peek() {
-            if name.eq_ignore_ascii_case(expected) {
-                self.next();
-                return true;
-            }
+        if let Some(Token::Ident(name)) = self.peek()
+            && name.eq_ignore_ascii_case(expected)
+        {
+            self.next();
+            return true;
         }
         false
     }
 
+    /// Remembers where a column was first referenced, using the span of the token just consumed.
+    fn record_column(&mut self, name: &str) {
+        if let Some(span) = self.position.checked_sub(1).and_then(|i| self.spans.get(i)) {
+            self.column_spans.record(name, *span);
+        }
+    }
+
     /// Checks for a token and consumes it if found.
     fn match_token(&mut self, expected: &Token) -> bool {
-        if let Some(tok) = self.peek() {
-            if tok == expected {
-                self.next();
-                return true;
-            }
+        if let Some(tok) = self.peek()
+            && tok == expected
+        {
+            self.next();
+            return true;
         }
         false
     }
@@ -245,3 +610,12 @@ impl Parser {
         }
     }
 }
+
+/// Converts an option value such as `";"` or `"\t"` into a single ASCII byte.
+fn single_byte(key: &str, value: &str) -> Result<u8, QueryError> {
+    match value.as_bytes() {
+        [b'\\', b't'] => Ok(b'\t'),
+        [b] if b.is_ascii() => Ok(*b),
+        _ => Err(QueryError::Expected(format!("Expected a single ASCII character for '{}'", key))),
+    }
+}
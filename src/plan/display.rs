@@ -0,0 +1,139 @@
+use super::{CompareOp, JoinKind, LogicalPlan, PlanExpr};
+use crate::ast::{Cluster, Pivot, Sample, SampleSize, Sort, SortDirection, Source, Unpivot, Value};
+use std::fmt;
+
+/// Renders the plan as an indented tree, one node per line, inputs below their consumer.
+impl fmt::Display for LogicalPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_tree(f, 0)
+    }
+}
+
+impl LogicalPlan {
+    fn write_tree(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        writeln!(f, "{:indent$}{}", "", self.label(), indent = depth * 2)?;
+        for input in self.inputs() {
+            input.write_tree(f, depth + 1)?;
+        }
+        Ok(())
+    }
+
+    /// One-line description of the node itself, without its inputs.
+    pub fn label(&self) -> String {
+        use LogicalPlan::*;
+        match self {
+            Scan { source } => scan_label(source),
+            Filter { predicate, .. } => format!("Filter {}", predicate),
+            Project { columns, .. } => format!("Project {}", columns.join(", ")),
+            Map { name, expr, .. } => format!("Map {} = {}", name, expr),
+            Aggregate { keys, aggregations, .. } => {
+                let aggs: Vec<String> = aggregations
+                    .iter()
+                    .map(|a| format!("{}({}) as {}", a.func.name(), a.column, a.alias))
+                    .collect();
+                format!("Aggregate {} by {}", aggs.join(", "), keys.join(", "))
+            }
+            Sort { sort, .. } => sort_label(sort),
+            Limit { n, .. } => format!("Limit {}", n),
+            Distinct { .. } => "Distinct".to_string(),
+            Join { on, kind, .. } => {
+                let kind = match kind {
+                    JoinKind::Inner => "inner",
+                    JoinKind::Left => "left",
+                };
+                format!("Join {} on {}", kind, on.join(", "))
+            }
+            Sample { sample, .. } => sample_label(sample),
+            Cluster { cluster, .. } => cluster_label(cluster),
+            Pivot { pivot, .. } => pivot_label(pivot),
+            Unpivot { unpivot, .. } => unpivot_label(unpivot),
+        }
+    }
+}
+
+pub(super) fn scan_label(source: &Source) -> String {
+    let paths: Vec<String> = source.paths.iter().map(|p| format!("{:?}", p)).collect();
+    format!("Scan {}", paths.join(", "))
+}
+
+pub(super) fn sort_label(sort: &Sort) -> String {
+    let direction = match sort.direction {
+        SortDirection::Asc => "asc",
+        SortDirection::Desc => "desc",
+    };
+    format!("Sort by {} {}", sort.column, direction)
+}
+
+pub(super) fn sample_label(sample: &Sample) -> String {
+    let size = match sample.size {
+        SampleSize::Rows(n) => format!("{} rows", n),
+        SampleSize::Percent(p) => format!("{}%", p),
+    };
+    match sample.seed {
+        Some(seed) => format!("Sample {} seed {}", size, seed),
+        None => format!("Sample {}", size),
+    }
+}
+
+pub(super) fn cluster_label(cluster: &Cluster) -> String {
+    format!("Cluster by {} into {} as {}", cluster.columns.join(", "), cluster.k, cluster.alias)
+}
+
+pub(super) fn pivot_label(pivot: &Pivot) -> String {
+    let mut label = format!("Pivot {}({}) by {}", pivot.agg.name(), pivot.values, pivot.columns);
+    if !pivot.index.is_empty() {
+        label.push_str(&format!(" for {}", pivot.index.join(", ")));
+    }
+    label
+}
+
+pub(super) fn unpivot_label(unpivot: &Unpivot) -> String {
+    format!("Unpivot {} into {}, {}", unpivot.columns.join(", "), unpivot.variable, unpivot.value)
+}
+
+/// Renders expressions in query syntax, e.g. `age > 25 and (city == "Recife" or active == true)`.
+impl fmt::Display for PlanExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlanExpr::Column(name) => write!(f, "{}", name),
+            PlanExpr::Literal(value) => write_value(f, value),
+            PlanExpr::Compare(op, lhs, rhs) => write!(f, "{} {} {}", lhs, op, rhs),
+            PlanExpr::And(lhs, rhs) => {
+                write_operand(f, lhs)?;
+                write!(f, " and ")?;
+                write_operand(f, rhs)
+            }
+            PlanExpr::Or(lhs, rhs) => write!(f, "{} or {}", lhs, rhs),
+        }
+    }
+}
+
+/// Writes an `and` operand, parenthesising `or` so precedence survives.
+fn write_operand(f: &mut fmt::Formatter<'_>, expr: &PlanExpr) -> fmt::Result {
+    match expr {
+        PlanExpr::Or(..) => write!(f, "({})", expr),
+        _ => write!(f, "{}", expr),
+    }
+}
+
+fn write_value(f: &mut fmt::Formatter<'_>, value: &Value) -> fmt::Result {
+    match value {
+        Value::String(s) => write!(f, "{:?}", s),
+        Value::Number(n) => write!(f, "{}", n),
+        Value::Float(x) => write!(f, "{:?}", x),
+        Value::Bool(b) => write!(f, "{}", b),
+    }
+}
+
+impl fmt::Display for CompareOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CompareOp::Eq => "==",
+            CompareOp::NotEq => "!=",
+            CompareOp::Gt => ">",
+            CompareOp::Lt => "<",
+            CompareOp::Gte => ">=",
+            CompareOp::Lte => "<=",
+        })
+    }
+}
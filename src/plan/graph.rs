@@ -0,0 +1,131 @@
+use super::display::{cluster_label, pivot_label, sample_label, scan_label, sort_label, unpivot_label};
+use super::{LogicalPlan, PlanExpr};
+use crate::ast::{Expr, Query, WriteMode};
+use std::fmt::Write;
+
+/// A labelled tree, the common shape rendered by `to_dot` and `to_mermaid`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphNode {
+    pub label: String,
+    pub children: Vec<GraphNode>,
+}
+
+impl GraphNode {
+    fn leaf(label: impl Into<String>) -> Self {
+        GraphNode { label: label.into(), children: Vec::new() }
+    }
+}
+
+/// One node per operator, with edges from each node to its inputs.
+impl From<&LogicalPlan> for GraphNode {
+    fn from(plan: &LogicalPlan) -> Self {
+        GraphNode {
+            label: plan.label(),
+            children: plan.inputs().into_iter().map(GraphNode::from).collect(),
+        }
+    }
+}
+
+/// One node per clause under a `Query` root; filter conditions are split at `and`/`or`.
+impl From<&Query> for GraphNode {
+    fn from(query: &Query) -> Self {
+        let mut clauses = vec![GraphNode::leaf(scan_label(&query.source))];
+        if let Some(expr) = &query.filter {
+            clauses.push(GraphNode { label: "Filter".to_string(), children: vec![expr_node(expr)] });
+        }
+        if let Some(sample) = &query.sample {
+            clauses.push(GraphNode::leaf(sample_label(sample)));
+        }
+        if let Some(cluster) = &query.cluster {
+            clauses.push(GraphNode::leaf(cluster_label(cluster)));
+        }
+        if let Some(pivot) = &query.pivot {
+            clauses.push(GraphNode::leaf(pivot_label(pivot)));
+        }
+        if let Some(unpivot) = &query.unpivot {
+            clauses.push(GraphNode::leaf(unpivot_label(unpivot)));
+        }
+        if !query.show.is_empty() {
+            clauses.push(GraphNode::leaf(format!("Show {}", query.show.join(", "))));
+        }
+        if let Some((name, expr)) = &query.map {
+            clauses.push(GraphNode { label: format!("Map {}", name), children: vec![expr_node(expr)] });
+        }
+        if query.unique {
+            clauses.push(GraphNode::leaf("Unique"));
+        }
+        if let Some(sort) = &query.sort {
+            clauses.push(GraphNode::leaf(sort_label(sort)));
+        }
+        if let Some(n) = query.cap {
+            clauses.push(GraphNode::leaf(format!("Cap {}", n)));
+        }
+        if let Some(write) = &query.write {
+            let mut label = format!("Write to {:?}", write.path);
+            if let Some(format) = write.format {
+                label.push_str(&format!(" format {}", format.name()));
+            }
+            if write.mode != WriteMode::Overwrite {
+                label.push_str(&format!(" mode {}", write.mode.name()));
+            }
+            if !write.partition_by.is_empty() {
+                label.push_str(&format!(" partition by {}", write.partition_by.join(", ")));
+            }
+            clauses.push(GraphNode::leaf(label));
+        }
+        GraphNode { label: "Query".to_string(), children: clauses }
+    }
+}
+
+fn expr_node(expr: &Expr) -> GraphNode {
+    match expr {
+        Expr::And(lhs, rhs) => GraphNode { label: "and".to_string(), children: vec![expr_node(lhs), expr_node(rhs)] },
+        Expr::Or(lhs, rhs) => GraphNode { label: "or".to_string(), children: vec![expr_node(lhs), expr_node(rhs)] },
+        comparison => match PlanExpr::try_from(comparison.clone()) {
+            Ok(expr) => GraphNode::leaf(expr.to_string()),
+            Err(_) => GraphNode::leaf("map expression"),
+        },
+    }
+}
+
+/// Renders the tree as a Graphviz `digraph`, root at the top.
+pub fn to_dot(root: &GraphNode) -> String {
+    let mut out = String::from("digraph plan {\n    node [shape=box, fontname=\"monospace\"];\n");
+    let mut next_id = 0;
+    write_dot(root, &mut out, &mut next_id);
+    out.push_str("}\n");
+    out
+}
+
+fn write_dot(node: &GraphNode, out: &mut String, next_id: &mut usize) -> usize {
+    let id = *next_id;
+    *next_id += 1;
+    let label = node.label.replace('\\', "\\\\").replace('"', "\\\"");
+    let _ = writeln!(out, "    n{} [label=\"{}\"];", id, label);
+    for child in &node.children {
+        let child_id = write_dot(child, out, next_id);
+        let _ = writeln!(out, "    n{} -> n{};", id, child_id);
+    }
+    id
+}
+
+/// Renders the tree as a Mermaid flowchart, root at the top.
+pub fn to_mermaid(root: &GraphNode) -> String {
+    let mut out = String::from("flowchart TD\n");
+    let mut next_id = 0;
+    write_mermaid(root, &mut out, &mut next_id);
+    out
+}
+
+fn write_mermaid(node: &GraphNode, out: &mut String, next_id: &mut usize) -> usize {
+    let id = *next_id;
+    *next_id += 1;
+    // Mermaid has no escape character inside quoted labels, only HTML entities.
+    let label = node.label.replace('"', "#quot;");
+    let _ = writeln!(out, "    n{}[\"{}\"]", id, label);
+    for child in &node.children {
+        let child_id = write_mermaid(child, out, next_id);
+        let _ = writeln!(out, "    n{} --> n{}", id, child_id);
+    }
+    id
+}
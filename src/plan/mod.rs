@@ -0,0 +1,203 @@
+mod display;
+mod graph;
+mod optimizer;
+
+use crate::ast::{AggFunc, Cluster, Expr, Pivot, Query, Sample, Sort, Source, Unpivot, Value};
+use crate::errors::QueryError;
+
+pub use graph::{to_dot, to_mermaid, GraphNode};
+pub use optimizer::optimize;
+
+/// Logical query plan: a tree of relational operators the AST lowers into
+/// and the engine executes. Each node reads from its `input`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogicalPlan {
+    Scan {
+        source: Source,
+    },
+    Filter {
+        input: Box<LogicalPlan>,
+        predicate: PlanExpr,
+    },
+    /// Keeps only the listed columns, in that order.
+    Project {
+        input: Box<LogicalPlan>,
+        columns: Vec<String>,
+    },
+    /// Adds (or replaces) the column `name`.
+    Map {
+        input: Box<LogicalPlan>,
+        name: String,
+        expr: PlanExpr,
+    },
+    /// One row per distinct combination of `keys`, in order of first appearance.
+    Aggregate {
+        input: Box<LogicalPlan>,
+        keys: Vec<String>,
+        aggregations: Vec<Aggregation>,
+    },
+    Sort {
+        input: Box<LogicalPlan>,
+        sort: Sort,
+    },
+    Limit {
+        input: Box<LogicalPlan>,
+        n: usize,
+    },
+    /// Drops duplicate rows, keeping the first occurrence.
+    Distinct {
+        input: Box<LogicalPlan>,
+    },
+    Join {
+        left: Box<LogicalPlan>,
+        right: Box<LogicalPlan>,
+        on: Vec<String>,
+        kind: JoinKind,
+    },
+    Sample {
+        input: Box<LogicalPlan>,
+        sample: Sample,
+    },
+    Cluster {
+        input: Box<LogicalPlan>,
+        cluster: Cluster,
+    },
+    Pivot {
+        input: Box<LogicalPlan>,
+        pivot: Pivot,
+    },
+    Unpivot {
+        input: Box<LogicalPlan>,
+        unpivot: Unpivot,
+    },
+}
+
+/// `func(column) as alias` inside an `Aggregate`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Aggregation {
+    pub func: AggFunc,
+    pub column: String,
+    pub alias: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JoinKind {
+    Inner,
+    Left,
+}
+
+/// Scalar expression evaluated per row.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlanExpr {
+    Column(String),
+    Literal(Value),
+    Compare(CompareOp, Box<PlanExpr>, Box<PlanExpr>),
+    And(Box<PlanExpr>, Box<PlanExpr>),
+    Or(Box<PlanExpr>, Box<PlanExpr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompareOp {
+    Eq,
+    NotEq,
+    Gt,
+    Lt,
+    Gte,
+    Lte,
+}
+
+impl LogicalPlan {
+    /// The node's inputs, left to right.
+    pub fn inputs(&self) -> Vec<&LogicalPlan> {
+        use LogicalPlan::*;
+        match self {
+            Scan { .. } => vec![],
+            Join { left, right, .. } => vec![left, right],
+            Filter { input, .. }
+            | Project { input, .. }
+            | Map { input, .. }
+            | Aggregate { input, .. }
+            | Sort { input, .. }
+            | Limit { input, .. }
+            | Distinct { input }
+            | Sample { input, .. }
+            | Cluster { input, .. }
+            | Pivot { input, .. }
+            | Unpivot { input, .. } => vec![input],
+        }
+    }
+}
+
+impl PlanExpr {
+    pub fn compare(op: CompareOp, column: &str, value: Value) -> Self {
+        PlanExpr::Compare(op, Box::new(PlanExpr::Column(column.to_string())), Box::new(PlanExpr::Literal(value)))
+    }
+
+    pub fn and(self, other: PlanExpr) -> Self {
+        PlanExpr::And(Box::new(self), Box::new(other))
+    }
+
+    pub fn or(self, other: PlanExpr) -> Self {
+        PlanExpr::Or(Box::new(self), Box::new(other))
+    }
+}
+
+impl TryFrom<Expr> for PlanExpr {
+    type Error = QueryError;
+
+    fn try_from(expr: Expr) -> Result<Self, QueryError> {
+        Ok(match expr {
+            Expr::Eq(column, value) => PlanExpr::compare(CompareOp::Eq, &column, value),
+            Expr::NotEq(column, value) => PlanExpr::compare(CompareOp::NotEq, &column, value),
+            Expr::Gt(column, value) => PlanExpr::compare(CompareOp::Gt, &column, value),
+            Expr::Lt(column, value) => PlanExpr::compare(CompareOp::Lt, &column, value),
+            Expr::Gte(column, value) => PlanExpr::compare(CompareOp::Gte, &column, value),
+            Expr::Lte(column, value) => PlanExpr::compare(CompareOp::Lte, &column, value),
+            Expr::And(lhs, rhs) => PlanExpr::try_from(*lhs)?.and(PlanExpr::try_from(*rhs)?),
+            Expr::Or(lhs, rhs) => PlanExpr::try_from(*lhs)?.or(PlanExpr::try_from(*rhs)?),
+            Expr::MapExpr(_, _) => {
+                return Err(QueryError::Unexpected("Map expressions not fully supported yet".into()));
+            }
+        })
+    }
+}
+
+/// Lowers a parsed query into a logical plan. Clauses apply in a fixed order
+/// regardless of how they were written: filter, sample, cluster, pivot,
+/// unpivot, show, map, unique, sort, cap.
+pub fn lower(query: Query) -> Result<LogicalPlan, QueryError> {
+    let mut plan = LogicalPlan::Scan { source: query.source };
+
+    if let Some(expr) = query.filter {
+        plan = LogicalPlan::Filter { input: Box::new(plan), predicate: expr.try_into()? };
+    }
+    if let Some(sample) = query.sample {
+        plan = LogicalPlan::Sample { input: Box::new(plan), sample };
+    }
+    if let Some(cluster) = query.cluster {
+        plan = LogicalPlan::Cluster { input: Box::new(plan), cluster };
+    }
+    if let Some(pivot) = query.pivot {
+        plan = LogicalPlan::Pivot { input: Box::new(plan), pivot };
+    }
+    if let Some(unpivot) = query.unpivot {
+        plan = LogicalPlan::Unpivot { input: Box::new(plan), unpivot };
+    }
+    if !query.show.is_empty() {
+        plan = LogicalPlan::Project { input: Box::new(plan), columns: query.show };
+    }
+    if let Some((name, expr)) = query.map {
+        plan = LogicalPlan::Map { input: Box::new(plan), name, expr: expr.try_into()? };
+    }
+    if query.unique {
+        plan = LogicalPlan::Distinct { input: Box::new(plan) };
+    }
+    if let Some(sort) = query.sort {
+        plan = LogicalPlan::Sort { input: Box::new(plan), sort };
+    }
+    if let Some(n) = query.cap {
+        plan = LogicalPlan::Limit { input: Box::new(plan), n };
+    }
+
+    Ok(plan)
+}
@@ -0,0 +1,169 @@
+use super::{CompareOp, LogicalPlan, PlanExpr};
+use crate::ast::Value;
+use std::cmp::Ordering;
+
+/// Applies the rewrite rules bottom-up until the plan stops changing.
+/// Every rule preserves the query's result, including row order.
+pub fn optimize(mut plan: LogicalPlan) -> LogicalPlan {
+    loop {
+        let next = rewrite(plan.clone());
+        if next == plan {
+            return next;
+        }
+        plan = next;
+    }
+}
+
+fn rewrite(plan: LogicalPlan) -> LogicalPlan {
+    use LogicalPlan::*;
+    match map_inputs(plan, rewrite) {
+        Filter { input, predicate } => match (*input, simplify(predicate)) {
+            (input, PlanExpr::Literal(Value::Bool(true))) => input,
+            // Adjacent filters become one conjunction, the inner predicate first.
+            (Filter { input, predicate: inner }, outer) => Filter { input, predicate: simplify(inner.and(outer)) },
+            (input, predicate) => Filter { input: Box::new(input), predicate },
+        },
+        Map { input, name, expr } => Map { input, name, expr: simplify(expr) },
+        Sort { input, sort } => Sort { input: Box::new(drop_sorts(*input, &sort.column)), sort },
+        Limit { input, n } => match *input {
+            Limit { input, n: inner } => Limit { input, n: n.min(inner) },
+            // Row-wise nodes don't change which rows come first, so the limit can run before them.
+            Project { input, columns } => Project { input: Box::new(Limit { input, n }), columns },
+            Map { input, name, expr } => Map { input: Box::new(Limit { input, n }), name, expr },
+            input => Limit { input: Box::new(input), n },
+        },
+        plan => plan,
+    }
+}
+
+/// Rebuilds a node with `f` applied to each of its inputs.
+fn map_inputs(plan: LogicalPlan, f: fn(LogicalPlan) -> LogicalPlan) -> LogicalPlan {
+    use LogicalPlan::*;
+    let apply = |input: Box<LogicalPlan>| Box::new(f(*input));
+    match plan {
+        Scan { source } => Scan { source },
+        Filter { input, predicate } => Filter { input: apply(input), predicate },
+        Project { input, columns } => Project { input: apply(input), columns },
+        Map { input, name, expr } => Map { input: apply(input), name, expr },
+        Aggregate { input, keys, aggregations } => Aggregate { input: apply(input), keys, aggregations },
+        Sort { input, sort } => Sort { input: apply(input), sort },
+        Limit { input, n } => Limit { input: apply(input), n },
+        Distinct { input } => Distinct { input: apply(input) },
+        Join { left, right, on, kind } => Join { left: apply(left), right: apply(right), on, kind },
+        Sample { input, sample } => Sample { input: apply(input), sample },
+        Cluster { input, cluster } => Cluster { input: apply(input), cluster },
+        Pivot { input, pivot } => Pivot { input: apply(input), pivot },
+        Unpivot { input, unpivot } => Unpivot { input: apply(input), unpivot },
+    }
+}
+
+/// Removes sorts on `column` below a sort on the same column. Sorts are stable,
+/// so the inner one only decides the order of ties, which the outer one keeps anyway.
+/// Stops at the first node that could reorder rows or redefine the column.
+fn drop_sorts(plan: LogicalPlan, column: &str) -> LogicalPlan {
+    use LogicalPlan::*;
+    match plan {
+        Sort { input, sort } if sort.column == column => drop_sorts(*input, column),
+        Filter { input, predicate } => Filter { input: Box::new(drop_sorts(*input, column)), predicate },
+        Project { input, columns } => Project { input: Box::new(drop_sorts(*input, column)), columns },
+        Map { input, name, expr } if name != column => Map { input: Box::new(drop_sorts(*input, column)), name, expr },
+        Distinct { input } => Distinct { input: Box::new(drop_sorts(*input, column)) },
+        plan => plan,
+    }
+}
+
+/// Folds constants and simplifies boolean structure. Rules follow Kleene
+/// logic (`false and null` is `false`), so results are the same with nulls.
+fn simplify(expr: PlanExpr) -> PlanExpr {
+    use PlanExpr::*;
+    match expr {
+        Compare(op, lhs, rhs) => match (simplify(*lhs), simplify(*rhs)) {
+            (Literal(a), Literal(b)) => match compare_values(&a, &b) {
+                Some(ordering) => Literal(Value::Bool(op.holds(ordering))),
+                None => Compare(op, Box::new(Literal(a)), Box::new(Literal(b))),
+            },
+            (lhs, rhs) => Compare(op, Box::new(lhs), Box::new(rhs)),
+        },
+        And(lhs, rhs) => match (simplify(*lhs), simplify(*rhs)) {
+            (Literal(Value::Bool(false)), _) | (_, Literal(Value::Bool(false))) => Literal(Value::Bool(false)),
+            (Literal(Value::Bool(true)), other) | (other, Literal(Value::Bool(true))) => other,
+            (lhs, rhs) if lhs == rhs => lhs,
+            (lhs, rhs) => merge_bounds(&lhs, &rhs, true).unwrap_or_else(|| lhs.and(rhs)),
+        },
+        Or(lhs, rhs) => match (simplify(*lhs), simplify(*rhs)) {
+            (Literal(Value::Bool(true)), _) | (_, Literal(Value::Bool(true))) => Literal(Value::Bool(true)),
+            (Literal(Value::Bool(false)), other) | (other, Literal(Value::Bool(false))) => other,
+            (lhs, rhs) if lhs == rhs => lhs,
+            (lhs, rhs) => merge_bounds(&lhs, &rhs, false).unwrap_or_else(|| lhs.or(rhs)),
+        },
+        expr => expr,
+    }
+}
+
+/// Two bounds in the same direction on the same column, e.g. `age > 18 and age > 21`,
+/// collapse to the tighter one (`and`) or the looser one (`or`).
+fn merge_bounds(lhs: &PlanExpr, rhs: &PlanExpr, tighter: bool) -> Option<PlanExpr> {
+    let (PlanExpr::Compare(op_a, col_a, lit_a), PlanExpr::Compare(op_b, col_b, lit_b)) = (lhs, rhs) else {
+        return None;
+    };
+    let (PlanExpr::Column(_), PlanExpr::Literal(a), PlanExpr::Literal(b)) = (&**col_a, &**lit_a, &**lit_b) else {
+        return None;
+    };
+    let lower = op_a.bound()?;
+    if col_a != col_b || op_b.bound()? != lower || !is_numeric(a) || !is_numeric(b) {
+        return None;
+    }
+
+    // A lower bound is tighter with the larger value, an upper bound with the
+    // smaller one; at equal values the strict operator wins.
+    let by_value = compare_values(a, b)?;
+    let a_is_tighter = match if lower { by_value } else { by_value.reverse() } {
+        Ordering::Greater => true,
+        Ordering::Less => false,
+        Ordering::Equal => op_a.is_strict() || !op_b.is_strict(),
+    };
+    Some(if a_is_tighter == tighter { lhs.clone() } else { rhs.clone() })
+}
+
+fn is_numeric(value: &Value) -> bool {
+    matches!(value, Value::Number(_) | Value::Float(_))
+}
+
+/// Orders two literals; numbers compare across integer and float.
+fn compare_values(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => Some(a.cmp(b)),
+        (Value::Number(a), Value::Float(b)) => (*a as f64).partial_cmp(b),
+        (Value::Float(a), Value::Number(b)) => a.partial_cmp(&(*b as f64)),
+        (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+impl CompareOp {
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            CompareOp::Eq => ordering == Ordering::Equal,
+            CompareOp::NotEq => ordering != Ordering::Equal,
+            CompareOp::Gt => ordering == Ordering::Greater,
+            CompareOp::Lt => ordering == Ordering::Less,
+            CompareOp::Gte => ordering != Ordering::Less,
+            CompareOp::Lte => ordering != Ordering::Greater,
+        }
+    }
+
+    /// `Some(true)` for `>`/`>=`, `Some(false)` for `<`/`<=`.
+    fn bound(self) -> Option<bool> {
+        match self {
+            CompareOp::Gt | CompareOp::Gte => Some(true),
+            CompareOp::Lt | CompareOp::Lte => Some(false),
+            CompareOp::Eq | CompareOp::NotEq => None,
+        }
+    }
+
+    fn is_strict(self) -> bool {
+        matches!(self, CompareOp::Gt | CompareOp::Lt)
+    }
+}
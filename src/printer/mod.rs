@@ -0,0 +1,385 @@
+use crate::ast::{
+    Cluster, CsvOptions, Expr, Pivot, Query, Sample, SampleSize, Sort, SortDirection, Source, SourceSchema, Span,
+    Unpivot, Value, WriteMode, WriteTo, STDIN_PATH,
+};
+use crate::errors::QueryError;
+use crate::lexer::{tokenize_with_comments, Token, KEYWORDS};
+use crate::parser::Parser;
+
+/// Indentation of the source's `with (...)` and `schema (...)` lines.
+const INDENT: &str = "    ";
+
+/// Clauses in the order they run, which is the order they are printed in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Clause {
+    Source,
+    Filter,
+    Sample,
+    Cluster,
+    Pivot,
+    Unpivot,
+    Show,
+    Map,
+    Unique,
+    Sort,
+    Cap,
+    Write,
+}
+
+impl Clause {
+    /// The clause a token starts, if it starts one.
+    fn starting_at(token: &Token) -> Option<Self> {
+        Some(match token {
+            Token::Explain | Token::Source => Clause::Source,
+            Token::Filter => Clause::Filter,
+            Token::Sample => Clause::Sample,
+            Token::Cluster => Clause::Cluster,
+            Token::Pivot => Clause::Pivot,
+            Token::Unpivot => Clause::Unpivot,
+            Token::Show => Clause::Show,
+            Token::Map => Clause::Map,
+            Token::Unique => Clause::Unique,
+            Token::Sort => Clause::Sort,
+            Token::Cap => Clause::Cap,
+            Token::Write => Clause::Write,
+            _ => return None,
+        })
+    }
+}
+
+/// Comments of the original text, attached to the clause they precede or end.
+#[derive(Debug, Default)]
+struct Comments {
+    /// Whole-line comments above a clause.
+    leading: Vec<(Clause, String)>,
+    /// A comment after a clause, on its last line.
+    trailing: Vec<(Clause, String)>,
+    /// Comments after the last clause.
+    footer: Vec<String>,
+}
+
+/// Prints a query in canonical form: lowercase keywords, one clause per line in
+/// the order the clauses run, and only the parentheses the grammar needs.
+/// Parsing the output gives back the same query.
+pub fn print_query(query: &Query) -> String {
+    print(query, &Comments::default())
+}
+
+/// Formats query text like `print_query`, keeping its `#` comments: a comment on
+/// its own line stays above the clause that follows it, one after a clause
+/// stays at the end of that clause's line.
+pub fn format_query(input: &str) -> Result<String, QueryError> {
+    let tokens = tokenize_with_comments(input)?;
+    let comments = collect_comments(input, &tokens);
+    let code = tokens.into_iter().filter(|(token, _)| !matches!(token, Token::Comment(_))).collect();
+    let query = Parser::with_spans(code).parse_query()?;
+    Ok(print(&query, &comments))
+}
+
+fn collect_comments(input: &str, tokens: &[(Token, Span)]) -> Comments {
+    let mut comments = Comments::default();
+    let mut clause = None;
+    let mut pending = Vec::new();
+    let mut previous_end = None;
+    for (token, span) in tokens {
+        match token {
+            Token::Comment(text) => {
+                let same_line = previous_end.is_some_and(|end| !input[end..span.start].contains('\n'));
+                match clause {
+                    Some(clause) if same_line => {
+                        // Only one comment fits at the end of the line; earlier ones move above the clause.
+                        if let Some(index) = comments.trailing.iter().position(|(c, _)| *c == clause) {
+                            let (_, earlier) = comments.trailing.remove(index);
+                            comments.leading.push((clause, earlier));
+                        }
+                        comments.trailing.push((clause, text.clone()));
+                    }
+                    _ => pending.push(text.clone()),
+                }
+            }
+            token => {
+                if let Some(next) = Clause::starting_at(token) {
+                    clause = Some(next);
+                    comments.leading.extend(pending.drain(..).map(|text| (next, text)));
+                }
+                previous_end = Some(span.end);
+            }
+        }
+    }
+    comments.footer = pending;
+    comments
+}
+
+fn print(query: &Query, comments: &Comments) -> String {
+    let mut clauses = vec![(Clause::Source, source(query))];
+    if let Some(expr) = &query.filter {
+        clauses.push((Clause::Filter, format!("filter {}", print_expr(expr))));
+    }
+    if let Some(sample) = &query.sample {
+        clauses.push((Clause::Sample, self::sample(sample)));
+    }
+    if let Some(cluster) = &query.cluster {
+        clauses.push((Clause::Cluster, self::cluster(cluster)));
+    }
+    if let Some(pivot) = &query.pivot {
+        clauses.push((Clause::Pivot, self::pivot(pivot)));
+    }
+    if let Some(unpivot) = &query.unpivot {
+        clauses.push((Clause::Unpivot, self::unpivot(unpivot)));
+    }
+    if !query.show.is_empty() {
+        clauses.push((Clause::Show, format!("show {}", names(&query.show))));
+    }
+    if let Some((name, expr)) = &query.map {
+        // `map` takes a single comparison unless the expression is parenthesised.
+        let expr = match expr {
+            Expr::And(..) | Expr::Or(..) => format!("({})", print_expr(expr)),
+            _ => print_expr(expr),
+        };
+        clauses.push((Clause::Map, format!("map {} = {}", self::name(name), expr)));
+    }
+    if query.unique {
+        clauses.push((Clause::Unique, "unique".to_string()));
+    }
+    if let Some(sort) = &query.sort {
+        clauses.push((Clause::Sort, self::sort(sort)));
+    }
+    if let Some(n) = query.cap {
+        clauses.push((Clause::Cap, format!("cap {}", n)));
+    }
+    if let Some(write) = &query.write {
+        clauses.push((Clause::Write, self::write(write)));
+    }
+
+    let mut out = String::new();
+    for (clause, text) in clauses {
+        for (_, comment) in comments.leading.iter().filter(|(c, _)| *c == clause) {
+            out.push_str(comment);
+            out.push('\n');
+        }
+        out.push_str(&text);
+        if let Some((_, comment)) = comments.trailing.iter().find(|(c, _)| *c == clause) {
+            out.push_str("  ");
+            out.push_str(comment);
+        }
+        out.push('\n');
+    }
+    // Comments on clauses that print nothing (e.g. an empty `show`) aren't lost.
+    for (_, comment) in comments.leading.iter().chain(&comments.trailing).filter(|(c, _)| !printed(query, *c)) {
+        out.push_str(comment);
+        out.push('\n');
+    }
+    for comment in &comments.footer {
+        out.push_str(comment);
+        out.push('\n');
+    }
+    out
+}
+
+/// Whether `print` writes the clause for this query.
+fn printed(query: &Query, clause: Clause) -> bool {
+    match clause {
+        Clause::Source => true,
+        Clause::Filter => query.filter.is_some(),
+        Clause::Sample => query.sample.is_some(),
+        Clause::Cluster => query.cluster.is_some(),
+        Clause::Pivot => query.pivot.is_some(),
+        Clause::Unpivot => query.unpivot.is_some(),
+        Clause::Show => !query.show.is_empty(),
+        Clause::Map => query.map.is_some(),
+        Clause::Unique => query.unique,
+        Clause::Sort => query.sort.is_some(),
+        Clause::Cap => query.cap.is_some(),
+        Clause::Write => query.write.is_some(),
+    }
+}
+
+/// `[explain] source "a.csv", stdin [as fmt]`, then indented `with (...)` and `schema (...)` lines.
+fn source(query: &Query) -> String {
+    let Source { paths, tag_file, format, options, schema } = &query.source;
+    let paths: Vec<String> = paths
+        .iter()
+        .map(|path| if path == STDIN_PATH { "stdin".to_string() } else { quote(path) })
+        .collect();
+    let mut out = format!("{}source {}", if query.explain { "explain " } else { "" }, paths.join(", "));
+    if let Some(format) = format {
+        out.push_str(&format!(" as {}", format.name()));
+    }
+    let options = source_options(*tag_file, options);
+    if !options.is_empty() {
+        out.push_str(&format!("\n{}with ({})", INDENT, options.join(", ")));
+    }
+    if let Some(schema) = schema {
+        out.push_str(&format!("\n{}{}", INDENT, self::schema(schema)));
+    }
+    out
+}
+
+/// The options that differ from the CSV defaults.
+fn source_options(tag_file: bool, options: &CsvOptions) -> Vec<String> {
+    let defaults = CsvOptions::default();
+    let mut out = Vec::new();
+    if tag_file {
+        out.push("source_file".to_string());
+    }
+    if options.delimiter != defaults.delimiter {
+        out.push(format!("delimiter {}", quote(&byte_option(options.delimiter))));
+    }
+    if options.quote != defaults.quote {
+        out.push(format!("quote {}", quote(&options.quote.map(byte_option).unwrap_or_default())));
+    }
+    if !options.header {
+        out.push("header false".to_string());
+    }
+    for null in &options.null_values {
+        out.push(format!("null {}", quote(null)));
+    }
+    if options.encoding != defaults.encoding {
+        out.push(format!("encoding {}", quote(options.encoding.name())));
+    }
+    if options.skip_rows > 0 {
+        out.push(format!("skip_rows {}", options.skip_rows));
+    }
+    if let Some(comment) = &options.comment {
+        out.push(format!("comment {}", quote(comment)));
+    }
+    out
+}
+
+/// A delimiter or quote byte as written in an option, with a tab as `\t`.
+fn byte_option(byte: u8) -> String {
+    match byte {
+        b'\t' => "\\t".to_string(),
+        byte => (byte as char).to_string(),
+    }
+}
+
+fn schema(schema: &SourceSchema) -> String {
+    let columns: Vec<String> = schema
+        .columns
+        .iter()
+        .map(|column| format!("{} {}", name(&column.name), column.ty.name()))
+        .collect();
+    format!("schema {}({})", if schema.exact { "exact " } else { "" }, columns.join(", "))
+}
+
+fn sample(sample: &Sample) -> String {
+    let mut out = match sample.size {
+        SampleSize::Rows(n) => format!("sample {} rows", n),
+        SampleSize::Percent(p) => format!("sample {}%", p),
+    };
+    if let Some(seed) = sample.seed {
+        out.push_str(&format!(" seed {}", seed));
+    }
+    out
+}
+
+fn cluster(cluster: &Cluster) -> String {
+    let mut out = format!("cluster by {} into {}", names(&cluster.columns), cluster.k);
+    if cluster.alias != "cluster" {
+        out.push_str(&format!(" as {}", name(&cluster.alias)));
+    }
+    if let Some(seed) = cluster.seed {
+        out.push_str(&format!(" seed {}", seed));
+    }
+    if cluster.centroids {
+        out.push_str(" with centroids");
+    }
+    out
+}
+
+fn pivot(pivot: &Pivot) -> String {
+    format!(
+        "pivot {}({}) by {} for {}",
+        pivot.agg.name(),
+        name(&pivot.values),
+        name(&pivot.columns),
+        names(&pivot.index)
+    )
+}
+
+fn unpivot(unpivot: &Unpivot) -> String {
+    format!("unpivot {} into {}, {}", names(&unpivot.columns), name(&unpivot.variable), name(&unpivot.value))
+}
+
+fn sort(sort: &Sort) -> String {
+    match sort.direction {
+        SortDirection::Asc => format!("sort by {}", name(&sort.column)),
+        SortDirection::Desc => format!("sort by {} desc", name(&sort.column)),
+    }
+}
+
+fn write(write: &WriteTo) -> String {
+    let mut out = format!("write to {}", quote(&write.path));
+    if let Some(format) = write.format {
+        out.push_str(&format!(" format {}", format.name()));
+    }
+    if write.mode != WriteMode::Overwrite {
+        out.push_str(&format!(" mode {}", write.mode.name()));
+    }
+    if !write.partition_by.is_empty() {
+        out.push_str(&format!(" partition by {}", names(&write.partition_by)));
+    }
+    out
+}
+
+/// Prints an expression with parentheses only where precedence (`and` binds
+/// tighter than `or`) or left-to-right grouping would otherwise change it.
+fn print_expr(expr: &Expr) -> String {
+    match expr {
+        Expr::Eq(column, value) => format!("{} == {}", name(column), literal(value)),
+        Expr::NotEq(column, value) => format!("{} != {}", name(column), literal(value)),
+        Expr::Gt(column, value) => format!("{} > {}", name(column), literal(value)),
+        Expr::Lt(column, value) => format!("{} < {}", name(column), literal(value)),
+        Expr::Gte(column, value) => format!("{} >= {}", name(column), literal(value)),
+        Expr::Lte(column, value) => format!("{} <= {}", name(column), literal(value)),
+        Expr::And(left, right) => {
+            let left = grouped(left, |e| matches!(e, Expr::Or(..)));
+            let right = grouped(right, |e| matches!(e, Expr::Or(..) | Expr::And(..)));
+            format!("{} and {}", left, right)
+        }
+        Expr::Or(left, right) => format!("{} or {}", print_expr(left), grouped(right, |e| matches!(e, Expr::Or(..)))),
+        // Not produced by the parser; the mapped expression is the closest text.
+        Expr::MapExpr(expr, _) => print_expr(expr),
+    }
+}
+
+fn grouped(expr: &Expr, needs_parens: impl Fn(&Expr) -> bool) -> String {
+    if needs_parens(expr) {
+        format!("({})", print_expr(expr))
+    } else {
+        print_expr(expr)
+    }
+}
+
+fn literal(value: &Value) -> String {
+    match value {
+        Value::String(s) => quote(s),
+        Value::Number(n) => n.to_string(),
+        // A float needs its decimal point, or it reads back as an integer.
+        Value::Float(f) if f.fract() == 0.0 => format!("{:.1}", f),
+        Value::Float(f) => f.to_string(),
+        Value::Bool(b) => b.to_string(),
+    }
+}
+
+/// String literals are kept exactly as written between the quotes.
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text)
+}
+
+/// A column name, in backticks unless it reads back as a plain identifier.
+fn name(column: &str) -> String {
+    let mut chars = column.chars();
+    let identifier = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if identifier && !KEYWORDS.contains(&column) {
+        column.to_string()
+    } else {
+        format!("`{}`", column)
+    }
+}
+
+fn names(columns: &[String]) -> String {
+    columns.iter().map(|column| name(column)).collect::<Vec<_>>().join(", ")
+}
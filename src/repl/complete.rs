@@ -0,0 +1,44 @@
+use crate::lexer::KEYWORDS;
+
+/// Meta-commands understood by the REPL, completed at the start of a line.
+pub const META_COMMANDS: &[&str] = &[".help", ".schema", ".tokens", ".ast", ".reload", ".quit", ".exit"];
+
+/// Completions for the word ending at the end of `line`: keywords and the
+/// given column names, or meta-commands when the line starts with `.`.
+/// Returns the char index where the word starts and the sorted candidates.
+/// Nothing is offered inside a string literal.
+pub fn complete(line: &str, columns: &[String]) -> (usize, Vec<String>) {
+    let chars: Vec<char> = line.chars().collect();
+    let start = chars
+        .iter()
+        .rposition(|c| !(c.is_alphanumeric() || *c == '_' || *c == '.'))
+        .map_or(0, |i| i + 1);
+    let word: String = chars[start..].iter().collect::<String>().to_lowercase();
+    let in_string = chars[..start].iter().filter(|c| **c == '"').count() % 2 == 1;
+    if in_string {
+        return (start, Vec::new());
+    }
+
+    let mut candidates: Vec<String> = if start == 0 && word.starts_with('.') {
+        META_COMMANDS.iter().map(|m| m.to_string()).collect()
+    } else {
+        KEYWORDS.iter().map(|k| k.to_string()).chain(columns.iter().cloned()).collect()
+    };
+    candidates.retain(|candidate| candidate.to_lowercase().starts_with(&word));
+    candidates.sort();
+    candidates.dedup();
+    (start, candidates)
+}
+
+/// The longest prefix shared by every candidate.
+pub fn common_prefix(candidates: &[String]) -> String {
+    let Some(first) = candidates.first() else {
+        return String::new();
+    };
+    let mut prefix: Vec<char> = first.chars().collect();
+    for candidate in &candidates[1..] {
+        let shared = prefix.iter().zip(candidate.chars()).take_while(|(a, b)| **a == *b).count();
+        prefix.truncate(shared);
+    }
+    prefix.into_iter().collect()
+}
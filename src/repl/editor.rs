@@ -0,0 +1,232 @@
+use super::complete::{common_prefix, complete};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::terminal;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::PathBuf;
+
+/// Most entries kept in memory and in the history file.
+const HISTORY_LIMIT: usize = 1000;
+
+/// What `Editor::read_line` got.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Line {
+    Text(String),
+    /// Ctrl-C: drop what has been typed so far.
+    Interrupted,
+    /// Ctrl-D on an empty line, or the end of piped input.
+    Eof,
+}
+
+/// A small line editor: cursor movement, history on the arrow keys and tab
+/// completion when stdin is a terminal, plain line reads when it isn't.
+pub struct Editor {
+    history: Vec<String>,
+    history_file: Option<PathBuf>,
+    interactive: bool,
+}
+
+/// A key press that means something to the editor.
+enum Key {
+    Char(char),
+    Enter,
+    Backspace,
+    Delete,
+    Tab,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    /// Ctrl-U: delete everything before the cursor.
+    KillLine,
+    Interrupt,
+    Eof,
+    Other,
+}
+
+/// Leaves raw mode when dropped, even on an early return.
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        Ok(RawMode)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+impl Editor {
+    /// Loads earlier history from `history_file`, if given, and appends new entries to it.
+    pub fn new(history_file: Option<PathBuf>) -> Self {
+        let history = history_file
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|text| text.lines().map(str::to_string).collect::<Vec<_>>())
+            .unwrap_or_default();
+        let skip = history.len().saturating_sub(HISTORY_LIMIT);
+        Editor {
+            history: history.into_iter().skip(skip).collect(),
+            history_file,
+            interactive: io::stdin().is_terminal() && io::stdout().is_terminal(),
+        }
+    }
+
+    pub fn add_history(&mut self, entry: &str) {
+        let entry = entry.trim();
+        if entry.is_empty() || self.history.last().is_some_and(|last| last == entry) {
+            return;
+        }
+        self.history.push(entry.to_string());
+        if self.history.len() > HISTORY_LIMIT {
+            self.history.remove(0);
+        }
+        if let Some(path) = &self.history_file
+            && let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path)
+        {
+            let _ = writeln!(file, "{}", entry);
+        }
+    }
+
+    /// Reads one line, completing words from the keywords and `columns` on Tab.
+    pub fn read_line(&mut self, prompt: &str, columns: &[String]) -> io::Result<Line> {
+        if !self.interactive {
+            let mut line = String::new();
+            return Ok(match io::stdin().lock().read_line(&mut line)? {
+                0 => Line::Eof,
+                _ => Line::Text(line.trim_end_matches(['\n', '\r']).to_string()),
+            });
+        }
+
+        let _raw = RawMode::enable()?;
+        let mut out = io::stdout().lock();
+        let mut buffer: Vec<char> = Vec::new();
+        let mut cursor = 0;
+        // Position in the history while browsing it, and the line being typed before that.
+        let mut browsing = self.history.len();
+        let mut draft: Vec<char> = Vec::new();
+        redraw(&mut out, prompt, &buffer, cursor)?;
+
+        loop {
+            match read_key()? {
+                Key::Char(c) => {
+                    buffer.insert(cursor, c);
+                    cursor += 1;
+                }
+                Key::Enter => {
+                    write!(out, "\r\n")?;
+                    out.flush()?;
+                    return Ok(Line::Text(buffer.into_iter().collect()));
+                }
+                Key::Backspace if cursor > 0 => {
+                    cursor -= 1;
+                    buffer.remove(cursor);
+                }
+                Key::Delete if cursor < buffer.len() => {
+                    buffer.remove(cursor);
+                }
+                Key::Left => cursor = cursor.saturating_sub(1),
+                Key::Right => cursor = (cursor + 1).min(buffer.len()),
+                Key::Home => cursor = 0,
+                Key::End => cursor = buffer.len(),
+                Key::KillLine => {
+                    buffer.drain(..cursor);
+                    cursor = 0;
+                }
+                Key::Up if browsing > 0 => {
+                    if browsing == self.history.len() {
+                        draft = buffer.clone();
+                    }
+                    browsing -= 1;
+                    buffer = self.history[browsing].chars().collect();
+                    cursor = buffer.len();
+                }
+                Key::Down if browsing < self.history.len() => {
+                    browsing += 1;
+                    buffer = match self.history.get(browsing) {
+                        Some(entry) => entry.chars().collect(),
+                        None => draft.clone(),
+                    };
+                    cursor = buffer.len();
+                }
+                Key::Tab => {
+                    let before: String = buffer[..cursor].iter().collect();
+                    let (start, candidates) = complete(&before, columns);
+                    let completion = match candidates.as_slice() {
+                        [] => None,
+                        [only] => Some(format!("{} ", only)),
+                        many => Some(common_prefix(many)).filter(|prefix| prefix.chars().count() > cursor - start),
+                    };
+                    match completion {
+                        Some(text) => {
+                            buffer.splice(start..cursor, text.chars());
+                            cursor = start + text.chars().count();
+                        }
+                        None if candidates.len() > 1 => write!(out, "\r\n{}\r\n", candidates.join("  "))?,
+                        None => write!(out, "\x07")?,
+                    }
+                }
+                Key::Interrupt => {
+                    write!(out, "^C\r\n")?;
+                    out.flush()?;
+                    return Ok(Line::Interrupted);
+                }
+                Key::Eof if buffer.is_empty() => {
+                    write!(out, "\r\n")?;
+                    out.flush()?;
+                    return Ok(Line::Eof);
+                }
+                _ => {}
+            }
+            redraw(&mut out, prompt, &buffer, cursor)?;
+        }
+    }
+}
+
+fn redraw(out: &mut impl Write, prompt: &str, buffer: &[char], cursor: usize) -> io::Result<()> {
+    let text: String = buffer.iter().collect();
+    write!(out, "\r{}{}\x1b[K", prompt, text)?;
+    if cursor < buffer.len() {
+        write!(out, "\x1b[{}D", buffer.len() - cursor)?;
+    }
+    out.flush()
+}
+
+/// Waits for the next key press; other terminal events are skipped.
+fn read_key() -> io::Result<Key> {
+    loop {
+        let Event::Key(KeyEvent { code, modifiers, kind, .. }) = event::read()? else {
+            continue;
+        };
+        if kind == KeyEventKind::Release {
+            continue;
+        }
+        let ctrl = modifiers.contains(KeyModifiers::CONTROL);
+        return Ok(match code {
+            KeyCode::Char('a') if ctrl => Key::Home,
+            KeyCode::Char('e') if ctrl => Key::End,
+            KeyCode::Char('c') if ctrl => Key::Interrupt,
+            KeyCode::Char('d') if ctrl => Key::Eof,
+            KeyCode::Char('u') if ctrl => Key::KillLine,
+            KeyCode::Char(_) if ctrl => Key::Other,
+            KeyCode::Char(c) => Key::Char(c),
+            KeyCode::Enter => Key::Enter,
+            KeyCode::Backspace => Key::Backspace,
+            KeyCode::Delete => Key::Delete,
+            KeyCode::Tab => Key::Tab,
+            KeyCode::Left => Key::Left,
+            KeyCode::Right => Key::Right,
+            KeyCode::Up => Key::Up,
+            KeyCode::Down => Key::Down,
+            KeyCode::Home => Key::Home,
+            KeyCode::End => Key::End,
+            _ => Key::Other,
+        });
+    }
+}
@@ -0,0 +1,200 @@
+mod complete;
+mod editor;
+
+use crate::analyzer::analyze;
+use crate::ast::{FileFormat, Source, STDIN_PATH};
+use crate::engine::{execute_query_with, explain_with, LoadedSources};
+use crate::{run_pipeline, PipelineResult};
+use crate::sink::{Sink, Table, TableFormat};
+use anyhow::Result;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+pub use complete::{common_prefix, complete, META_COMMANDS};
+pub use editor::{Editor, Line};
+
+const HELP: &str = "\
+End a query with a blank line. Commands:
+  .schema   columns and types of the sources loaded so far
+  .tokens   toggle printing each query's tokens
+  .ast      toggle printing each query's AST
+  .reload   forget loaded sources so the next query reads the files again
+  .quit     leave (also .exit, or Ctrl-D)
+";
+
+/// State kept between queries typed into the REPL.
+#[derive(Debug, Default)]
+pub struct Session {
+    show_tokens: bool,
+    show_ast: bool,
+    /// Sources read so far, kept in memory for the next queries.
+    sources: LoadedSources,
+    /// Column names of those sources, for completion.
+    columns: Vec<String>,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// Runs a meta-command or a query and returns what to print.
+    pub fn eval(&mut self, input: &str) -> Result<String> {
+        match input.trim() {
+            ".help" => Ok(HELP.to_string()),
+            ".schema" => self.schema(),
+            ".tokens" => {
+                self.show_tokens = !self.show_tokens;
+                Ok(format!("Printing tokens {}\n", on_off(self.show_tokens)))
+            }
+            ".ast" => {
+                self.show_ast = !self.show_ast;
+                Ok(format!("Printing the AST {}\n", on_off(self.show_ast)))
+            }
+            ".reload" => {
+                self.sources.clear();
+                self.columns.clear();
+                Ok("Sources will be read again\n".to_string())
+            }
+            command if command.starts_with('.') => {
+                Err(anyhow::anyhow!("Unknown command '{}'; .help lists the commands", command))
+            }
+            query => self.run(query),
+        }
+    }
+
+    fn run(&mut self, input: &str) -> Result<String> {
+        let mut out = String::new();
+        let result = run_pipeline(input, false);
+        if self.show_tokens {
+            let tokens = match &result {
+                Ok(result) => &result.tokens,
+                Err(failure) => &failure.tokens,
+            };
+            out.push_str("# Tokens:\n");
+            for token in tokens {
+                writeln!(out, "{:?}", token)?;
+            }
+        }
+        // The tokens go with a parse error, as they help explain it.
+        let PipelineResult { ast: query, column_spans, .. } =
+            result.map_err(|failure| anyhow::anyhow!("{}{}", out, failure.error.render(input)))?;
+        if self.show_ast {
+            writeln!(out, "# AST:\n{:#?}", query)?;
+        }
+
+        if query.source.paths.iter().any(|path| path == STDIN_PATH) {
+            return Err(anyhow::anyhow!("The REPL reads queries from stdin, so it can't be a source; save the data to a file"));
+        }
+        self.load(&query.source)?;
+        analyze(&query, &column_spans).map_err(|e| anyhow::anyhow!(e.render(input)))?;
+        if query.explain {
+            out.push_str(&explain_with(query, &self.sources)?);
+            return Ok(out);
+        }
+
+        let write = query.write.clone();
+        let mut df = execute_query_with(query, &self.sources)?;
+        match write {
+            Some(write) => {
+                let format = write.format.unwrap_or_else(|| FileFormat::from_path(&write.path));
+                let sink = Sink::new(format).with_mode(write.mode);
+                if write.partition_by.is_empty() {
+                    sink.write_file(&mut df, Path::new(&write.path))?;
+                } else {
+                    sink.write_partitioned(&df, Path::new(&write.path), &write.partition_by)?;
+                }
+                writeln!(out, "Exported result to {}", write.path)?;
+            }
+            None => out.push_str(&Table::new(TableFormat::Table).render(&df)),
+        }
+        Ok(out)
+    }
+
+    /// Keeps a source in memory and learns its columns.
+    fn load(&mut self, source: &Source) -> Result<()> {
+        if self.sources.get(source).is_some() {
+            return Ok(());
+        }
+        let df = self.sources.load(source)?;
+        for name in df.get_column_names() {
+            if !self.columns.iter().any(|known| known == name) {
+                self.columns.push(name.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    fn schema(&self) -> Result<String> {
+        if self.sources.is_empty() {
+            return Ok("No sources loaded yet\n".to_string());
+        }
+        let mut out = String::new();
+        for (source, df) in self.sources.iter() {
+            let paths: Vec<String> = source.paths.iter().map(|p| format!("{:?}", p)).collect();
+            writeln!(out, "{}", paths.join(", "))?;
+            let width = df.get_column_names().iter().map(|n| n.chars().count()).max().unwrap_or(0);
+            for column in df.get_columns() {
+                writeln!(out, "  {:width$}  {}", column.name(), column.dtype(), width = width)?;
+            }
+        }
+        Ok(out)
+    }
+}
+
+fn on_off(on: bool) -> &'static str {
+    if on { "on" } else { "off" }
+}
+
+/// Runs the interactive loop until `.quit` or end of input. History is kept
+/// in `~/.csv_query_history`.
+pub fn run() -> Result<()> {
+    let history = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".csv_query_history"));
+    let mut editor = Editor::new(history);
+    let mut session = Session::new();
+    println!("csv-query REPL. End a query with a blank line; .help lists commands.");
+
+    while let Some(statement) = read_statement(&mut editor, &session)? {
+        if matches!(statement.trim(), ".quit" | ".exit") {
+            break;
+        }
+        match session.eval(&statement) {
+            Ok(out) => print!("{}", out),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+    Ok(())
+}
+
+/// Reads lines until a blank one ends the query; a meta-command is a single line.
+/// `None` at the end of input.
+fn read_statement(editor: &mut Editor, session: &Session) -> Result<Option<String>> {
+    let mut lines: Vec<String> = Vec::new();
+    loop {
+        let prompt = if lines.is_empty() { "query> " } else { "  ...> " };
+        match editor.read_line(prompt, session.columns())? {
+            Line::Eof if lines.is_empty() => return Ok(None),
+            Line::Eof => break,
+            Line::Interrupted => lines.clear(),
+            Line::Text(line) if line.trim().is_empty() => {
+                if !lines.is_empty() {
+                    break;
+                }
+            }
+            Line::Text(line) if lines.is_empty() && line.trim_start().starts_with('.') => {
+                editor.add_history(&line);
+                return Ok(Some(line));
+            }
+            Line::Text(line) => {
+                // History keeps the lines as typed, so a `#` comment only covers its own line.
+                editor.add_history(&line);
+                lines.push(line);
+            }
+        }
+    }
+    Ok(Some(lines.join("\n")))
+}
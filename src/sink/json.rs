@@ -0,0 +1,135 @@
+use anyhow::Result;
+use polars::export::chrono::{NaiveDate, NaiveTime, TimeDelta};
+use polars::prelude::*;
+use serde_json::{Map, Number, Value};
+use std::io::Write;
+
+/// Writes `df` as one pretty-printed JSON array of row objects, keys in
+/// column order, ending with a newline.
+pub(super) fn write_array<W: Write>(df: &DataFrame, out: &mut W) -> Result<()> {
+    let columns = df.get_columns();
+    let mut records = Vec::with_capacity(df.height());
+    for i in 0..df.height() {
+        let mut record = Map::new();
+        for column in columns {
+            record.insert(column.name().to_string(), to_json(column.get(i)?));
+        }
+        records.push(Value::Object(record));
+    }
+    serde_json::to_writer_pretty(&mut *out, &records)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+/// Maps a cell to the JSON value closest to its type: numbers stay numbers,
+/// nulls become `null`, temporal values ISO-8601 strings, lists arrays and
+/// structs objects. Non-finite floats, and temporal values chrono can't
+/// represent, have no JSON form and become `null`; decimals become strings,
+/// as a JSON number would round them to a float.
+fn to_json(value: AnyValue) -> Value {
+    match value {
+        AnyValue::Null => Value::Null,
+        AnyValue::Boolean(b) => Value::Bool(b),
+        AnyValue::String(s) => Value::String(s.to_string()),
+        AnyValue::StringOwned(s) => Value::String(s.to_string()),
+        AnyValue::UInt8(n) => n.into(),
+        AnyValue::UInt16(n) => n.into(),
+        AnyValue::UInt32(n) => n.into(),
+        AnyValue::UInt64(n) => n.into(),
+        AnyValue::Int8(n) => n.into(),
+        AnyValue::Int16(n) => n.into(),
+        AnyValue::Int32(n) => n.into(),
+        AnyValue::Int64(n) => n.into(),
+        AnyValue::Float32(f) => float(f as f64),
+        AnyValue::Float64(f) => float(f),
+        AnyValue::Decimal(n, scale) => Value::String(decimal(n, scale)),
+        AnyValue::Date(days) => Value::String(date(days)),
+        AnyValue::Datetime(ticks, unit, zone) => match datetime(ticks, unit) {
+            // Zoned datetimes are stored as UTC instants.
+            Some(text) => Value::String(if zone.is_some() { format!("{}Z", text) } else { text }),
+            None => Value::Null,
+        },
+        AnyValue::Time(nanos) => time(nanos).map_or(Value::Null, Value::String),
+        AnyValue::Duration(ticks, unit) => Value::String(duration(ticks, unit)),
+        AnyValue::List(series) => Value::Array(series.iter().map(to_json).collect()),
+        AnyValue::Struct(_, _, fields) => {
+            let mut values = Vec::new();
+            value._materialize_struct_av(&mut values);
+            object(fields, values)
+        }
+        AnyValue::StructOwned(owned) => {
+            let (values, fields) = *owned;
+            object(&fields, values)
+        }
+        AnyValue::Binary(bytes) => bytes.iter().copied().map(Value::from).collect(),
+        AnyValue::BinaryOwned(bytes) => bytes.into_iter().map(Value::from).collect(),
+    }
+}
+
+fn object(fields: &[Field], values: Vec<AnyValue>) -> Value {
+    let entries = fields.iter().zip(values).map(|(field, value)| (field.name().to_string(), to_json(value)));
+    Value::Object(entries.collect())
+}
+
+fn float(f: f64) -> Value {
+    Number::from_f64(f).map_or(Value::Null, Value::Number)
+}
+
+/// The exact digits of a decimal, e.g. `-12.50` for -1250 at scale 2.
+fn decimal(n: i128, scale: usize) -> String {
+    let sign = if n < 0 { "-" } else { "" };
+    let digits = format!("{:0width$}", n.unsigned_abs(), width = scale + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - scale);
+    match fraction {
+        "" => format!("{}{}", sign, whole),
+        _ => format!("{}{}.{}", sign, whole, fraction),
+    }
+}
+
+fn epoch() -> NaiveDate {
+    NaiveDate::from_ymd_opt(1970, 1, 1).unwrap_or_default()
+}
+
+/// `YYYY-MM-DD` for a day count since 1970-01-01.
+fn date(days: i32) -> String {
+    (epoch() + TimeDelta::days(days as i64)).format("%Y-%m-%d").to_string()
+}
+
+/// `YYYY-MM-DDTHH:MM:SS`, with a fraction only when there is one; `None`
+/// past chrono's range of years.
+fn datetime(ticks: i64, unit: TimeUnit) -> Option<String> {
+    let offset = match unit {
+        TimeUnit::Nanoseconds => TimeDelta::nanoseconds(ticks),
+        TimeUnit::Microseconds => TimeDelta::microseconds(ticks),
+        TimeUnit::Milliseconds => TimeDelta::milliseconds(ticks),
+    };
+    epoch()
+        .and_hms_opt(0, 0, 0)
+        .and_then(|midnight| midnight.checked_add_signed(offset))
+        .map(|instant| instant.format("%Y-%m-%dT%H:%M:%S%.f").to_string())
+}
+
+fn time(nanos: i64) -> Option<String> {
+    let seconds = (nanos / 1_000_000_000) as u32;
+    let fraction = (nanos % 1_000_000_000) as u32;
+    NaiveTime::from_num_seconds_from_midnight_opt(seconds, fraction)
+        .map(|t| t.format("%H:%M:%S%.f").to_string())
+}
+
+/// ISO-8601 duration in seconds, e.g. `PT90S` or `PT0.5S`.
+fn duration(ticks: i64, unit: TimeUnit) -> String {
+    let per_second = match unit {
+        TimeUnit::Nanoseconds => 1_000_000_000,
+        TimeUnit::Microseconds => 1_000_000,
+        TimeUnit::Milliseconds => 1_000,
+    };
+    let sign = if ticks < 0 { "-" } else { "" };
+    let ticks = ticks.unsigned_abs();
+    let fraction = ticks % per_second;
+    if fraction == 0 {
+        return format!("{}PT{}S", sign, ticks / per_second);
+    }
+    let digits = per_second.ilog10() as usize;
+    let fraction = format!("{:0width$}", fraction, width = digits);
+    format!("{}PT{}.{}S", sign, ticks / per_second, fraction.trim_end_matches('0'))
+}
@@ -0,0 +1,361 @@
+mod json;
+mod table;
+
+use crate::ast::{FileFormat, WriteMode};
+use anyhow::Result;
+use polars::prelude::*;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+pub use table::{Table, TableFormat, DEFAULT_MAX_ROWS, DEFAULT_MAX_WIDTH};
+
+/// Where a query result goes: an output format plus the options that apply to it.
+/// Library users and the CLI share this, e.g.
+/// `Sink::new(FileFormat::Csv).with_csv(CsvSinkOptions::excel())`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sink {
+    pub format: FileFormat,
+    pub csv: CsvSinkOptions,
+    /// Parquet and Arrow IPC compression; `None` keeps the format's default
+    /// (zstd for Parquet, uncompressed for IPC).
+    pub compression: Option<Compression>,
+    /// What to do when the output file already exists.
+    pub mode: WriteMode,
+}
+
+/// CSV dialect for written files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvSinkOptions {
+    pub delimiter: u8,
+    pub quote: u8,
+    pub quote_style: QuoteStyle,
+    pub header: bool,
+    /// Starts the file with a UTF-8 byte order mark so Excel detects the encoding.
+    pub bom: bool,
+}
+
+impl Default for CsvSinkOptions {
+    fn default() -> Self {
+        CsvSinkOptions { delimiter: b',', quote: b'"', quote_style: QuoteStyle::Necessary, header: true, bom: false }
+    }
+}
+
+impl CsvSinkOptions {
+    /// `;`-separated with a BOM, as Excel expects in locales that use a decimal comma.
+    pub fn excel() -> Self {
+        CsvSinkOptions { delimiter: b';', bom: true, ..Default::default() }
+    }
+}
+
+/// When CSV fields are wrapped in quotes.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum QuoteStyle {
+    /// Only fields containing the delimiter, a quote or a line break.
+    #[default]
+    Necessary,
+    Always,
+    /// Every field that isn't a number.
+    NonNumeric,
+    /// Never, even if the output becomes ambiguous.
+    Never,
+}
+
+impl QuoteStyle {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_lowercase().replace('_', "-").as_str() {
+            "necessary" => QuoteStyle::Necessary,
+            "always" => QuoteStyle::Always,
+            "non-numeric" => QuoteStyle::NonNumeric,
+            "never" => QuoteStyle::Never,
+            _ => return None,
+        })
+    }
+
+    fn to_polars(self) -> polars::prelude::QuoteStyle {
+        match self {
+            QuoteStyle::Necessary => polars::prelude::QuoteStyle::Necessary,
+            QuoteStyle::Always => polars::prelude::QuoteStyle::Always,
+            QuoteStyle::NonNumeric => polars::prelude::QuoteStyle::NonNumeric,
+            QuoteStyle::Never => polars::prelude::QuoteStyle::Never,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    Uncompressed,
+    Snappy,
+    Gzip,
+    Lz4,
+    Zstd,
+}
+
+impl Compression {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "none" | "uncompressed" => Compression::Uncompressed,
+            "snappy" => Compression::Snappy,
+            "gzip" | "gz" => Compression::Gzip,
+            "lz4" => Compression::Lz4,
+            "zstd" => Compression::Zstd,
+            _ => return None,
+        })
+    }
+
+    fn parquet(self) -> ParquetCompression {
+        match self {
+            Compression::Uncompressed => ParquetCompression::Uncompressed,
+            Compression::Snappy => ParquetCompression::Snappy,
+            Compression::Gzip => ParquetCompression::Gzip(None),
+            Compression::Lz4 => ParquetCompression::Lz4Raw,
+            Compression::Zstd => ParquetCompression::Zstd(None),
+        }
+    }
+
+    /// Arrow IPC only knows LZ4 and zstd.
+    fn ipc(self) -> Result<Option<IpcCompression>> {
+        match self {
+            Compression::Uncompressed => Ok(None),
+            Compression::Lz4 => Ok(Some(IpcCompression::LZ4)),
+            Compression::Zstd => Ok(Some(IpcCompression::ZSTD)),
+            other => Err(anyhow::anyhow!("Arrow IPC supports lz4 or zstd compression, not {:?}", other)),
+        }
+    }
+}
+
+impl Sink {
+    /// A sink with default options for `format`.
+    pub fn new(format: FileFormat) -> Self {
+        Sink { format, csv: CsvSinkOptions::default(), compression: None, mode: WriteMode::Overwrite }
+    }
+
+    pub fn with_csv(mut self, csv: CsvSinkOptions) -> Self {
+        self.csv = csv;
+        self
+    }
+
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
+    pub fn with_mode(mut self, mode: WriteMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Writes `df` to `out` in the sink's format.
+    pub fn write<W: Write>(&self, df: &mut DataFrame, mut out: W) -> Result<()> {
+        match self.format {
+            FileFormat::Csv => CsvWriter::new(&mut out)
+                .include_bom(self.csv.bom)
+                .include_header(self.csv.header)
+                .with_separator(self.csv.delimiter)
+                .with_quote_char(self.csv.quote)
+                .with_quote_style(self.csv.quote_style.to_polars())
+                .finish(df)?,
+            FileFormat::Json => json::write_array(df, &mut out)?,
+            FileFormat::Ndjson => JsonWriter::new(&mut out).with_json_format(JsonFormat::JsonLines).finish(df)?,
+            FileFormat::Parquet => {
+                ParquetWriter::new(&mut out).with_compression(self.parquet_compression()).finish(df)?;
+            }
+            FileFormat::Ipc => IpcWriter::new(&mut out).with_compression(self.ipc_compression()?).finish(df)?,
+        }
+        Ok(out.flush()?)
+    }
+
+    /// Writes `df` to a file at `path`, creating missing parent directories.
+    /// In append mode, rows are added after an existing CSV's rows without
+    /// repeating its header; the columns are put in the header's order, and
+    /// appending fails if the header names other columns.
+    pub fn write_file(&self, df: &mut DataFrame, path: &Path) -> Result<()> {
+        // Check options before touching the file system so a bad one leaves nothing behind.
+        self.check()?;
+        self.check_target(path)?;
+        create_parent_dir(path)?;
+        if self.mode == WriteMode::Append && path.exists() {
+            let file = OpenOptions::new().append(true).open(path)?;
+            if file.metadata()?.len() > 0 && self.format == FileFormat::Csv {
+                let mut rows = if self.csv.header { df.select(self.existing_header(df, path)?)? } else { df.clone() };
+                let csv = CsvSinkOptions { header: false, bom: false, ..self.csv.clone() };
+                return self.clone().with_csv(csv).write(&mut rows, file);
+            }
+            return self.write(df, file);
+        }
+        self.write(df, File::create(path)?)
+    }
+
+    /// Writes one file per distinct combination of `columns` under `dir`, in
+    /// Hive-style directories such as `year=2024/city=Recife/part.parquet`.
+    /// The partition values live in the path, so those columns are left out of
+    /// the files. Returns the files written, in order of first appearance.
+    /// In overwrite mode the partitions of earlier writes are removed first, so
+    /// no stale `part` file survives under a value that's gone from the result;
+    /// other files in `dir` are left alone.
+    pub fn write_partitioned(&self, df: &DataFrame, dir: &Path, columns: &[String]) -> Result<Vec<PathBuf>> {
+        self.check()?;
+        for column in columns {
+            if df.column(column).is_err() {
+                return Err(anyhow::anyhow!("Unknown partition column '{}'", column));
+            }
+        }
+        let mut parts = Vec::new();
+        for part in df.partition_by_stable(columns, true)? {
+            let mut path = dir.to_path_buf();
+            for column in columns {
+                path.push(format!("{}={}", column, partition_value(part.column(column)?.get(0)?)));
+            }
+            path.push(format!("part.{}", extension(self.format)));
+            self.check_target(&path)?;
+            parts.push((path, part.drop_many(columns)));
+        }
+        if self.mode == WriteMode::Overwrite
+            && let Some(first) = columns.first()
+        {
+            remove_partitions(dir, first)?;
+        }
+        let mut written = Vec::new();
+        for (path, mut part) in parts {
+            self.write_file(&mut part, &path)?;
+            written.push(path);
+        }
+        Ok(written)
+    }
+
+    /// Fails if the options don't fit the format, e.g. gzip for Arrow IPC.
+    pub fn check(&self) -> Result<()> {
+        if self.format == FileFormat::Ipc {
+            self.ipc_compression()?;
+        }
+        if self.mode == WriteMode::Append && !matches!(self.format, FileFormat::Csv | FileFormat::Ndjson) {
+            return Err(anyhow::anyhow!("Only CSV and NDJSON output can be appended to, not {}", self.format.name()));
+        }
+        Ok(())
+    }
+
+    /// The column names in the header of the CSV file at `path`, which must be
+    /// those of `df` in any order.
+    fn existing_header(&self, df: &DataFrame, path: &Path) -> Result<Vec<String>> {
+        let existing = CsvReader::from_path(path)?
+            .with_separator(self.csv.delimiter)
+            .with_quote_char(Some(self.csv.quote))
+            .with_n_rows(Some(0))
+            .finish()?;
+        let header: Vec<String> = existing.get_column_names().iter().map(|name| name.to_string()).collect();
+        let columns = df.get_column_names();
+        if header.len() != columns.len() || columns.iter().any(|name| !header.iter().any(|known| known == name)) {
+            return Err(anyhow::anyhow!(
+                "Can't append to '{}': its header has columns [{}] but the result has [{}]",
+                path.display(),
+                header.join(", "),
+                columns.join(", ")
+            ));
+        }
+        Ok(header)
+    }
+
+    /// Fails if the mode forbids writing to `path` as it is now.
+    fn check_target(&self, path: &Path) -> Result<()> {
+        if self.mode == WriteMode::ErrorIfExists && path.exists() {
+            return Err(anyhow::anyhow!("'{}' already exists (write mode error_if_exists)", path.display()));
+        }
+        Ok(())
+    }
+
+    /// Runs `lf` on the streaming engine straight into a file at `path`.
+    /// Fails for JSON arrays, for appending and for plans Polars can't sink.
+    pub fn stream(&self, lf: LazyFrame, path: &Path) -> Result<()> {
+        self.check()?;
+        self.check_target(path)?;
+        if self.mode == WriteMode::Append {
+            return Err(anyhow::anyhow!("Appending can't be streamed"));
+        }
+        create_parent_dir(path)?;
+        let target = path.to_path_buf();
+        match self.format {
+            FileFormat::Csv => {
+                let mut options = CsvWriterOptions {
+                    include_bom: self.csv.bom,
+                    include_header: self.csv.header,
+                    ..Default::default()
+                };
+                options.serialize_options.separator = self.csv.delimiter;
+                options.serialize_options.quote_char = self.csv.quote;
+                options.serialize_options.quote_style = self.csv.quote_style.to_polars();
+                lf.sink_csv(target, options)?
+            }
+            FileFormat::Parquet => lf.sink_parquet(
+                target,
+                ParquetWriteOptions { compression: self.parquet_compression(), ..Default::default() },
+            )?,
+            FileFormat::Ipc => {
+                lf.sink_ipc(target, IpcWriterOptions { compression: self.ipc_compression()?, ..Default::default() })?
+            }
+            FileFormat::Ndjson => lf.sink_json(target, JsonWriterOptions::default())?,
+            FileFormat::Json => {
+                return Err(anyhow::anyhow!("JSON arrays can't be streamed; write NDJSON instead"));
+            }
+        }
+        Ok(())
+    }
+
+    fn parquet_compression(&self) -> ParquetCompression {
+        self.compression.map(Compression::parquet).unwrap_or_default()
+    }
+
+    fn ipc_compression(&self) -> Result<Option<IpcCompression>> {
+        self.compression.map_or(Ok(None), Compression::ipc)
+    }
+}
+
+/// A partition value as a directory name: nulls use Hive's default
+/// partition name and characters that can't appear in paths are %-escaped.
+fn partition_value(value: AnyValue) -> String {
+    let text = match value {
+        AnyValue::Null => return "__HIVE_DEFAULT_PARTITION__".to_string(),
+        AnyValue::String(s) => s.to_string(),
+        other => other.to_string(),
+    };
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c.is_control() || "/\\=%:*?\"<>|#".contains(c) {
+            escaped.push_str(&format!("%{:02X}", c as u32));
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+fn extension(format: FileFormat) -> &'static str {
+    match format {
+        FileFormat::Csv => "csv",
+        FileFormat::Parquet => "parquet",
+        FileFormat::Ndjson => "ndjson",
+        FileFormat::Json => "json",
+        FileFormat::Ipc => "arrow",
+    }
+}
+
+/// Removes the `column=value` directories an earlier partitioned write left in `dir`.
+fn remove_partitions(dir: &Path, column: &str) -> Result<()> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(());
+    };
+    let prefix = format!("{}=", column);
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() && entry.file_name().to_string_lossy().starts_with(&prefix) {
+            fs::remove_dir_all(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+fn create_parent_dir(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => fs::create_dir_all(dir),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,189 @@
+use anyhow::Result;
+use polars::prelude::*;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// Rows shown by default before the rest is summarised as "… N more rows".
+pub const DEFAULT_MAX_ROWS: usize = 50;
+/// Characters shown per cell by default before it is cut with "…".
+pub const DEFAULT_MAX_WIDTH: usize = 40;
+
+/// Human-readable renderings of a result, for terminals, wikis and e-mail.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TableFormat {
+    /// Aligned plain text.
+    Table,
+    /// GitHub-flavoured Markdown table.
+    Markdown,
+    Html,
+}
+
+impl TableFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "table" | "txt" | "text" => TableFormat::Table,
+            "markdown" | "md" => TableFormat::Markdown,
+            "html" | "htm" => TableFormat::Html,
+            _ => return None,
+        })
+    }
+}
+
+/// Renders a result as a table, truncating long results unless built with [`Table::full`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    pub format: TableFormat,
+    /// `None` shows every row.
+    pub max_rows: Option<usize>,
+    /// `None` never cuts cells.
+    pub max_width: Option<usize>,
+}
+
+impl Table {
+    pub fn new(format: TableFormat) -> Self {
+        Table { format, max_rows: Some(DEFAULT_MAX_ROWS), max_width: Some(DEFAULT_MAX_WIDTH) }
+    }
+
+    /// Every row, every character.
+    pub fn full(mut self) -> Self {
+        self.max_rows = None;
+        self.max_width = None;
+        self
+    }
+
+    pub fn render(&self, df: &DataFrame) -> String {
+        let shown = self.max_rows.map_or(df.height(), |max| max.min(df.height()));
+        let headers: Vec<String> = df.get_column_names().iter().map(|name| self.cut(name)).collect();
+        let numeric: Vec<bool> = df.get_columns().iter().map(|c| c.dtype().is_numeric()).collect();
+        let rows: Vec<Vec<Option<String>>> = (0..shown)
+            .map(|i| df.get_columns().iter().map(|column| self.cell(column, i)).collect())
+            .collect();
+
+        let mut out = match self.format {
+            TableFormat::Table => text(&headers, &rows, &numeric),
+            TableFormat::Markdown => markdown(&headers, &rows, &numeric),
+            TableFormat::Html => html(&headers, &rows, &numeric),
+        };
+        let hidden = df.height() - shown;
+        if hidden > 0 {
+            let note = format!("… {} more row{}", hidden, if hidden == 1 { "" } else { "s" });
+            match self.format {
+                TableFormat::Html => out.push_str(&format!("<p>{}</p>\n", note)),
+                TableFormat::Table | TableFormat::Markdown => out.push_str(&format!("\n{}\n", note)),
+            }
+        }
+        out
+    }
+
+    pub fn write<W: Write>(&self, df: &DataFrame, mut out: W) -> Result<()> {
+        out.write_all(self.render(df).as_bytes())?;
+        Ok(out.flush()?)
+    }
+
+    /// Writes the rendering to a file at `path`, creating missing parent directories.
+    pub fn write_file(&self, df: &DataFrame, path: &Path) -> Result<()> {
+        super::create_parent_dir(path)?;
+        Ok(fs::write(path, self.render(df))?)
+    }
+
+    /// Cell text, or `None` for a null.
+    fn cell(&self, column: &Series, row: usize) -> Option<String> {
+        match column.get(row).ok()? {
+            AnyValue::Null => None,
+            value => Some(self.cut(value.get_str().map_or_else(|| value.to_string(), str::to_string).as_str())),
+        }
+    }
+
+    fn cut(&self, text: &str) -> String {
+        match self.max_width {
+            Some(max) if text.chars().count() > max => {
+                let kept: String = text.chars().take(max.saturating_sub(1)).collect();
+                format!("{}…", kept)
+            }
+            _ => text.to_string(),
+        }
+    }
+}
+
+fn text(headers: &[String], rows: &[Vec<Option<String>>], numeric: &[bool]) -> String {
+    let rows: Vec<Vec<&str>> = rows
+        .iter()
+        .map(|row| row.iter().map(|cell| cell.as_deref().unwrap_or("null")).collect())
+        .collect();
+    let widths: Vec<usize> = headers
+        .iter()
+        .enumerate()
+        .map(|(i, header)| rows.iter().map(|row| width(row[i])).chain([width(header)]).max().unwrap_or(0))
+        .collect();
+
+    let mut out = String::new();
+    let header: Vec<&str> = headers.iter().map(String::as_str).collect();
+    push_text_row(&mut out, &header, &widths, numeric);
+    let rule: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+    out.push_str(rule.join("  ").trim_end());
+    out.push('\n');
+    for row in &rows {
+        push_text_row(&mut out, row, &widths, numeric);
+    }
+    out
+}
+
+fn push_text_row(out: &mut String, cells: &[&str], widths: &[usize], numeric: &[bool]) {
+    let padded: Vec<String> = cells
+        .iter()
+        .zip(widths)
+        .zip(numeric)
+        .map(|((cell, column_width), right)| {
+            let padding = " ".repeat(column_width - width(cell));
+            if *right {
+                format!("{}{}", padding, cell)
+            } else {
+                format!("{}{}", cell, padding)
+            }
+        })
+        .collect();
+    out.push_str(padded.join("  ").trim_end());
+    out.push('\n');
+}
+
+fn width(text: &str) -> usize {
+    text.chars().count()
+}
+
+fn markdown(headers: &[String], rows: &[Vec<Option<String>>], numeric: &[bool]) -> String {
+    let escape = |text: &str| text.replace('|', "\\|").replace('\n', "<br>");
+    let line = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+
+    let mut out = line(headers.iter().map(|h| escape(h)).collect());
+    out.push_str(&line(numeric.iter().map(|right| if *right { "---:" } else { "---" }.to_string()).collect()));
+    for row in rows {
+        out.push_str(&line(row.iter().map(|cell| escape(cell.as_deref().unwrap_or(""))).collect()));
+    }
+    out
+}
+
+fn html(headers: &[String], rows: &[Vec<Option<String>>], numeric: &[bool]) -> String {
+    let escape = |text: &str| {
+        text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+    };
+    let cell = |tag: &str, text: &str, right: bool| {
+        let align = if right { " align=\"right\"" } else { "" };
+        format!("<{tag}{align}>{}</{tag}>", escape(text))
+    };
+
+    let mut out = String::from("<table>\n  <thead>\n    <tr>");
+    for (header, right) in headers.iter().zip(numeric) {
+        out.push_str(&cell("th", header, *right));
+    }
+    out.push_str("</tr>\n  </thead>\n  <tbody>\n");
+    for row in rows {
+        out.push_str("    <tr>");
+        for (value, right) in row.iter().zip(numeric) {
+            out.push_str(&cell("td", value.as_deref().unwrap_or(""), *right));
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("  </tbody>\n</table>\n");
+    out
+}
@@ -0,0 +1,120 @@
+use query_compiler::analyzer::analyze;
+use query_compiler::ast::{ColumnSpans, Query, Span};
+use query_compiler::errors::QueryError;
+use query_compiler::lexer::tokenize_spanned;
+use query_compiler::parser::Parser;
+use std::fs::File;
+use std::io::Write;
+
+/// Writes a small CSV with integer, string and float columns.
+fn create_people_csv(path: &str) {
+    let mut file = File::create(path).unwrap();
+    writeln!(file, "name,age,city,price").unwrap();
+    writeln!(file, "Alice,30,Recife,10.5").unwrap();
+    writeln!(file, "Bob,22,Olinda,8.0").unwrap();
+}
+
+fn parse(input: &str) -> (Query, ColumnSpans) {
+    let mut parser = Parser::with_spans(tokenize_spanned(input));
+    let query = parser.parse_query().expect("should parse");
+    (query, parser.column_spans().clone())
+}
+
+#[test]
+fn accepts_valid_query() {
+    create_people_csv("tests/test_analyze_ok.csv");
+
+    let (query, spans) = parse(r#"
+        source "tests/test_analyze_ok.csv"
+        filter age > 25 and price <= 10.5 or city == "Recife"
+        show name, age
+        map adult = age >= 18
+        sort by adult desc
+    "#);
+
+    analyze(&query, &spans).expect("should pass");
+}
+
+#[test]
+fn reports_unknown_column_with_span() {
+    create_people_csv("tests/test_analyze_unknown.csv");
+
+    let input = "source \"tests/test_analyze_unknown.csv\"\nfilter agee > 30";
+    let (query, spans) = parse(input);
+    let err = analyze(&query, &spans).unwrap_err();
+
+    match &err {
+        QueryError::Semantic { message, span } => {
+            assert!(message.contains("Unknown column 'agee'"), "{}", message);
+            assert_eq!(*span, Some(Span { start: 47, end: 51 }));
+        }
+        other => panic!("unexpected error: {:?}", other),
+    }
+    assert_eq!(
+        err.render(input),
+        "Semantic error at line 2, column 8: Unknown column 'agee'; did you mean 'age'?\n  filter agee > 30\n         ^^^^"
+    );
+}
+
+#[test]
+fn rejects_mismatched_comparison() {
+    create_people_csv("tests/test_analyze_types.csv");
+
+    let (query, spans) = parse(r#"source "tests/test_analyze_types.csv" filter city > 3"#);
+    let err = analyze(&query, &spans).unwrap_err().to_string();
+
+    assert!(err.contains("Cannot compare column 'city' (str) with integer 3"), "{}", err);
+}
+
+#[test]
+fn checks_columns_through_reshapes() {
+    create_people_csv("tests/test_analyze_reshape.csv");
+
+    // Columns created by `cluster` can be shown; `cluster` itself needs numbers.
+    let (query, spans) = parse(r#"source "tests/test_analyze_reshape.csv" cluster by age into 2 as segment show name, segment"#);
+    analyze(&query, &spans).expect("should pass");
+
+    let (query, spans) = parse(r#"source "tests/test_analyze_reshape.csv" cluster by city into 2"#);
+    assert!(analyze(&query, &spans).unwrap_err().to_string().contains("numeric"));
+
+    // After a pivot the new columns depend on the data, so they aren't checked.
+    let (query, spans) = parse(r#"source "tests/test_analyze_reshape.csv" pivot sum(price) by name for city show city, Alice"#);
+    analyze(&query, &spans).expect("should pass");
+
+    let (query, spans) = parse(r#"source "tests/test_analyze_reshape.csv" pivot sum(name) by age for city"#);
+    assert!(analyze(&query, &spans).unwrap_err().to_string().contains("sum() can't aggregate column 'name'"));
+}
+
+#[test]
+fn lists_columns_when_nothing_is_close() {
+    create_people_csv("tests/test_analyze_far.csv");
+
+    let (query, spans) = parse(r#"source "tests/test_analyze_far.csv" show zipcode"#);
+    let err = analyze(&query, &spans).unwrap_err().to_string();
+
+    assert!(err.contains("Unknown column 'zipcode' (available: name, age, city, price)"), "{}", err);
+}
+
+#[test]
+fn explains_rejected_coercions() {
+    create_people_csv("tests/test_analyze_coerce.csv");
+
+    let (query, spans) = parse(r#"source "tests/test_analyze_coerce.csv" filter age >= "18" and price < 10"#);
+    analyze(&query, &spans).expect("quoted numbers and int/float mixes are allowed");
+
+    let (query, spans) = parse(r#"source "tests/test_analyze_coerce.csv" filter price > "cheap""#);
+    let err = analyze(&query, &spans).unwrap_err().to_string();
+    assert!(err.contains("Cannot compare column 'price' (f64) with string \"cheap\": \"cheap\" is not a number"), "{}", err);
+}
+
+#[test]
+fn checks_partition_columns_against_the_result() {
+    create_people_csv("tests/test_analyze_partition.csv");
+
+    let (query, spans) = parse(r#"source "tests/test_analyze_partition.csv" show name, city write to "out/" partition by city"#);
+    analyze(&query, &spans).expect("should pass");
+
+    let (query, spans) = parse(r#"source "tests/test_analyze_partition.csv" show name write to "out/" partition by city"#);
+    let err = analyze(&query, &spans).unwrap_err().to_string();
+    assert!(err.contains("Unknown column 'city'"), "{}", err);
+}
@@ -0,0 +1,237 @@
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Writes a query over a fresh CSV and returns the query file's path.
+fn write_query(name: &str) -> String {
+    let csv = format!("tests/test_cli_{}.csv", name);
+    fs::write(&csv, "name,age\nAlice,30\nBob,22\n").unwrap();
+    let query = format!("tests/test_cli_{}.query", name);
+    fs::write(&query, format!("source \"{}\"\nfilter age > 25\n", csv)).unwrap();
+    query
+}
+
+fn run(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_query_compiler")).args(args).output().unwrap()
+}
+
+fn run_with_stdin(args: &[&str], stdin: &str) -> std::process::Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_query_compiler"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn writes_to_the_given_output_path() {
+    let query = write_query("path");
+    let out = "tests/test_cli_out/nested/result.data";
+    let _ = fs::remove_dir_all("tests/test_cli_out");
+
+    let output = run(&["--query", &query, "--output", out, "--format", "ndjson"]);
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(fs::read_to_string(out).unwrap(), "{\"name\":\"Alice\",\"age\":30}\n");
+}
+
+#[test]
+fn writes_to_stdout_for_dash() {
+    let query = write_query("stdout");
+
+    let output = run(&["--query", &query, "--output", "-"]);
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "name,age\nAlice,30\n");
+}
+
+#[test]
+fn fails_on_unsupported_format_without_creating_file() {
+    let query = write_query("unsupported");
+    let out = "tests/test_cli_unsupported.xlsx";
+    let _ = fs::remove_file(out);
+
+    let output = run(&["--query", &query, "--output", out]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unsupported output format 'xlsx'"));
+    assert!(!std::path::Path::new(out).exists());
+}
+
+#[test]
+fn writes_excel_friendly_csv() {
+    let query = write_query("excel");
+
+    let output = run(&["--query", &query, "--output", "-", "--delimiter", ";", "--bom", "--quote-style", "always"]);
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(output.stdout, b"\xEF\xBB\xBF\"name\";\"age\"\n\"Alice\";\"30\"\n");
+}
+
+#[test]
+fn prints_markdown_to_stdout_without_output_path() {
+    let query = write_query("markdown");
+
+    let output = run(&["--query", &query, "--format", "markdown"]);
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "| name | age |\n| --- | ---: |\n| Alice | 30 |\n");
+}
+
+#[test]
+fn pivots_to_stdout_without_extra_lines() {
+    fs::write("tests/test_cli_pivot.csv", "month,city,amount\nJan,Recife,1\nJan,Natal,2\nFeb,Recife,3\n").unwrap();
+
+    let output = run(&["-e", r#"source "tests/test_cli_pivot.csv" pivot sum(amount) by month for city"#, "--format", "csv"]);
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.lines().next(), Some("city,Feb,Jan"), "{}", stdout);
+}
+
+#[test]
+fn writes_partitions_from_the_command_line() {
+    let query = write_query("partition");
+    let out = "tests/test_cli_partitions";
+    let _ = fs::remove_dir_all(out);
+
+    let output = run(&["--query", &query, "--output", out, "--partition-by", "name", "--format", "ndjson"]);
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(fs::read_to_string(format!("{}/name=Alice/part.ndjson", out)).unwrap(), "{\"age\":30}\n");
+}
+
+#[test]
+fn follows_the_query_write_clause() {
+    let csv = "tests/test_cli_write.csv";
+    fs::write(csv, "name,age\nAlice,30\nBob,22\n").unwrap();
+    let out = "tests/test_cli_write_out.log";
+    let _ = fs::remove_file(out);
+    let query = "tests/test_cli_write.query";
+    fs::write(query, format!("source \"{}\"\nfilter age > 25\nwrite to \"{}\" format ndjson mode append\n", csv, out))
+        .unwrap();
+
+    assert!(run(&["--query", query]).status.success());
+    assert!(run(&["--query", query]).status.success());
+
+    assert_eq!(fs::read_to_string(out).unwrap(), "{\"name\":\"Alice\",\"age\":30}\n".repeat(2));
+}
+
+#[test]
+fn runs_inline_queries_over_stdin_data() {
+    let output = run_with_stdin(
+        &["-e", "source stdin filter age > 25 show name", "--output", "-"],
+        "name,age\nAlice,30\nBob,22\n",
+    );
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "name\nAlice\n");
+}
+
+#[test]
+fn reads_the_query_from_stdin() {
+    let csv = "tests/test_cli_stdin_query.csv";
+    fs::write(csv, "name,age\nAlice,30\nBob,22\n").unwrap();
+
+    let output = run_with_stdin(&["--query", "-", "--format", "csv"], &format!("source \"{}\" filter age < 25", csv));
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "name,age\nBob,22\n");
+
+    let output = run_with_stdin(&["--query", "-"], "source \"-\"");
+    assert!(!output.status.success());
+}
+
+#[test]
+fn runs_through_the_run_subcommand() {
+    let query = write_query("run");
+
+    let output = run(&["run", "--query", &query, "--format", "csv"]);
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "name,age\nAlice,30\n");
+}
+
+#[test]
+fn checks_a_query_without_running_it() {
+    let query = write_query("check");
+
+    let output = run(&["check", "--query", &query]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "OK\n");
+
+    let output = run(&["check", "-e", "source \"tests/test_cli_check.csv\" filter agee > 3"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unknown column 'agee'"));
+}
+
+#[test]
+fn prints_tokens_of_a_query_that_does_not_parse() {
+    let output = run(&["check", "--tokens", "-e", "source \"x.csv\" filterr age"]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Ident(\"filterr\")"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unknown clause 'filterr'"));
+}
+
+#[test]
+fn explains_and_draws_plans() {
+    let query = write_query("explain");
+
+    let output = run(&["explain", "--query", &query]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Optimized"));
+
+    let output = run(&["explain", "--query", &query, "--plan-format", "mermaid"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("flowchart"));
+}
+
+#[test]
+fn prints_the_schema_and_head_of_a_file() {
+    let csv = "tests/test_cli_schema.csv";
+    fs::write(csv, "name,age\nAlice,30\nBob,22\nCarol,41\n").unwrap();
+
+    let output = run(&["schema", csv]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "name  str\nage   i64\n");
+
+    let output = run(&["head", csv, "-n", "2"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "name   age\n-----  ---\nAlice   30\nBob     22\n");
+}
+
+#[test]
+fn formats_query_files_in_place_and_checks_them() {
+    let query = "tests/test_cli_fmt.query";
+    fs::write(query, "source \"people.csv\" # nightly\nshow name filter age>25\n").unwrap();
+
+    let output = run(&["fmt", "--check", query]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("tests/test_cli_fmt.query is not formatted"));
+
+    assert!(run(&["fmt", query]).status.success());
+    assert_eq!(fs::read_to_string(query).unwrap(), "source \"people.csv\"  # nightly\nfilter age > 25\nshow name\n");
+    assert!(run(&["fmt", "--check", query]).status.success());
+
+    let output = run_with_stdin(&["fmt"], "source stdin cap 3");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "source stdin\ncap 3\n");
+}
+
+#[test]
+fn leaves_queries_with_unrecognised_text_alone() {
+    let query = "tests/test_cli_fmt_lex.query";
+    let text = "source \"people.csv\" filter age == -3 @\n";
+    fs::write(query, text).unwrap();
+
+    for args in [vec!["fmt", query], vec!["fmt", "--check", query]] {
+        let output = run(&args);
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Unrecognised text '-'"));
+    }
+    assert_eq!(fs::read_to_string(query).unwrap(), text);
+}
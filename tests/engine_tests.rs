@@ -1,5 +1,7 @@
-use query_compiler::engine::execute_query;
-use query_compiler::ast::{Expr, Query, Value, Sort, SortDirection};
+use query_compiler::engine::{build_lazy, execute_plan, explain, execute_query, execute_query_streaming, sink_query, SOURCE_FILE_COLUMN};
+use query_compiler::ast::{Expr, Query, Source, SourceSchema, ColumnDef, ColumnType, CsvOptions, Encoding, FileFormat, Value, Sort, SortDirection, Sample, SampleSize, Cluster, Pivot, Unpivot, AggFunc};
+use query_compiler::sink::Sink;
+use query_compiler::plan::{Aggregation, JoinKind, LogicalPlan};
 use std::fs::File;
 use std::io::Write;
 
@@ -15,7 +17,7 @@ fn create_test_csv(path: &str) {
 /// Builds a minimal query object for tests.
 fn build_query() -> Query {
     Query {
-        source: "tests/test_data.csv".to_string(),
+        source: "tests/test_data.csv".into(),
         filter: Some(Expr::Gt("age".to_string(), Value::Number(25))),
         show: vec!["name".to_string(), "age".to_string()],
         sort: Some(Sort {
@@ -25,6 +27,7 @@ fn build_query() -> Query {
         cap: None,
         map: None,
         unique: false,
+        ..Default::default()
     }
 }
 
@@ -55,3 +58,399 @@ fn returns_empty_if_no_rows_match() {
 
     assert_eq!(df.height(), 0);
 }
+
+#[test]
+fn samples_are_reproducible_with_seed() {
+    create_test_csv("tests/test_sample.csv");
+
+    let mut query = build_query();
+    query.source = "tests/test_sample.csv".into();
+    query.filter = None;
+    query.sort = None;
+    query.sample = Some(Sample { size: SampleSize::Rows(2), seed: Some(42) });
+
+    let first = execute_query(query.clone()).expect("should succeed");
+    let second = execute_query(query).expect("should succeed");
+
+    assert_eq!(first.height(), 2);
+    assert!(first.equals(&second));
+}
+
+#[test]
+fn clusters_rows_into_segments() {
+    create_test_csv("tests/test_cluster.csv");
+
+    let mut query = build_query();
+    query.source = "tests/test_cluster.csv".into();
+    query.filter = None;
+    query.show = vec!["name".to_string(), "age".to_string(), "segment".to_string()];
+    query.cluster = Some(Cluster {
+        columns: vec!["age".to_string()],
+        k: 2,
+        alias: "segment".to_string(),
+        seed: Some(1),
+        centroids: false,
+    });
+
+    let df = execute_query(query).expect("should succeed");
+    let segment = df.column("segment").unwrap();
+
+    // Sorted by age desc: Carol (40), Alice (30), Bob (22).
+    assert_ne!(segment.get(0).unwrap(), segment.get(2).unwrap());
+    assert_eq!(df.width(), 3);
+}
+
+/// Creates a long-format sales CSV for reshape tests.
+fn create_sales_csv(path: &str) {
+    let mut file = File::create(path).unwrap();
+    writeln!(file, "city,month,amount").unwrap();
+    writeln!(file, "Recife,jan,10").unwrap();
+    writeln!(file, "Recife,jan,5").unwrap();
+    writeln!(file, "Recife,feb,7").unwrap();
+    writeln!(file, "Olinda,jan,3").unwrap();
+}
+
+#[test]
+fn pivots_and_unpivots_sales() {
+    create_sales_csv("tests/test_pivot.csv");
+
+    let query = Query {
+        source: "tests/test_pivot.csv".into(),
+        pivot: Some(Pivot {
+            agg: AggFunc::Sum,
+            values: "amount".to_string(),
+            columns: "month".to_string(),
+            index: vec!["city".to_string()],
+        }),
+        sort: Some(Sort { column: "city".to_string(), direction: SortDirection::Desc }),
+        ..Default::default()
+    };
+    let wide = execute_query(query.clone()).expect("should pivot");
+
+    assert_eq!(wide.shape(), (2, 3));
+    assert_eq!(wide.column("jan").unwrap().get(0).unwrap().to_string(), "15");
+
+    let long = execute_query(Query {
+        unpivot: Some(Unpivot {
+            columns: vec!["jan".to_string(), "feb".to_string()],
+            variable: "month".to_string(),
+            value: "total".to_string(),
+        }),
+        ..query
+    })
+    .expect("should unpivot");
+
+    assert_eq!(long.shape(), (4, 3));
+    assert_eq!(long.get_column_names(), vec!["city", "month", "total"]);
+}
+
+#[test]
+fn appends_sources_and_aligns_columns() {
+    create_test_csv("tests/test_union_a.csv");
+    let mut file = File::create("tests/test_union_b.csv").unwrap();
+    writeln!(file, "name,city,score").unwrap();
+    writeln!(file, "Dave,Natal,7.5").unwrap();
+
+    let query = Query {
+        source: Source {
+            paths: vec!["tests/test_union_a.csv".into(), "tests/test_union_?.csv".into()],
+            tag_file: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let df = execute_query(query).expect("should succeed");
+
+    // a.csv is listed explicitly and matched again by the pattern.
+    assert_eq!(df.height(), 7);
+    assert_eq!(df.get_column_names(), vec!["name", "age", "city", SOURCE_FILE_COLUMN, "score"]);
+    assert_eq!(df.column("age").unwrap().null_count(), 1);
+    assert_eq!(df.column(SOURCE_FILE_COLUMN).unwrap().str_value(6).unwrap(), "tests/test_union_b.csv");
+}
+
+#[test]
+fn reads_semicolon_latin1_exports() {
+    // "São Paulo" encoded as ISO-8859-1, preceded by a title line.
+    let mut bytes = b"Relatorio de clientes\nname;city;age\nAna;S".to_vec();
+    bytes.extend_from_slice(&[0xE3, b'o']);
+    bytes.extend_from_slice(b" Paulo;NA\nBia;Recife;31\n");
+    std::fs::write("tests/test_latin1.csv", bytes).unwrap();
+
+    let query = Query {
+        source: Source {
+            paths: vec!["tests/test_latin1.csv".into()],
+            options: CsvOptions {
+                delimiter: b';',
+                null_values: vec!["NA".into()],
+                encoding: Encoding::Latin1,
+                skip_rows: 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let df = execute_query(query).expect("should succeed");
+
+    assert_eq!(df.shape(), (2, 3));
+    assert_eq!(df.column("city").unwrap().str_value(0).unwrap(), "São Paulo");
+    assert_eq!(df.column("age").unwrap().null_count(), 1);
+}
+
+#[test]
+fn reads_parquet_and_ndjson_sources() {
+    use polars::prelude::{df, ParquetWriter};
+
+    let mut frame = df!("name" => ["Ana", "Bia"], "id" => ["007", "042"]).unwrap();
+    ParquetWriter::new(File::create("tests/test_source.parquet").unwrap())
+        .finish(&mut frame)
+        .unwrap();
+    let parquet = execute_query(Query { source: "tests/test_source.parquet".into(), ..Default::default() })
+        .expect("should read parquet");
+    assert!(parquet.equals(&frame));
+
+    std::fs::write("tests/test_source.log", "{\"name\":\"Ana\",\"age\":30}\n{\"name\":\"Bia\",\"age\":22}\n").unwrap();
+    let query = Query {
+        source: Source {
+            paths: vec!["tests/test_source.log".into()],
+            format: Some(FileFormat::Ndjson),
+            ..Default::default()
+        },
+        filter: Some(Expr::Gt("age".to_string(), Value::Number(25))),
+        ..Default::default()
+    };
+    let ndjson = execute_query(query).expect("should read ndjson");
+    assert_eq!(ndjson.height(), 1);
+}
+
+/// Builds a query over `path` with the given declared column types.
+fn schema_query(path: &str, columns: &[(&str, ColumnType)]) -> Query {
+    Query {
+        source: Source {
+            paths: vec![path.into()],
+            schema: Some(SourceSchema {
+                columns: columns.iter().map(|(name, ty)| ColumnDef { name: name.to_string(), ty: *ty }).collect(),
+                exact: false,
+            }),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+#[test]
+fn applies_declared_schema_types() {
+    std::fs::write("tests/test_schema.csv", "id,age,joined,active\n007,30,2024-01-05,yes\n042,22,2023-11-30,no\n").unwrap();
+
+    let query = schema_query("tests/test_schema.csv", &[
+        ("id", ColumnType::String),
+        ("joined", ColumnType::Date),
+        ("active", ColumnType::Bool),
+    ]);
+    let df = execute_query(query).expect("should succeed");
+
+    assert_eq!(df.column("id").unwrap().str_value(0).unwrap(), "007");
+    assert_eq!(df.column("joined").unwrap().dtype(), &polars::prelude::DataType::Date);
+    assert_eq!(df.column("active").unwrap().bool().unwrap().get(1), Some(false));
+}
+
+#[test]
+fn parses_declared_datetimes_in_the_literal_formats() {
+    std::fs::write(
+        "tests/test_schema_datetime.csv",
+        "seen\n2024-01-01 10:00:00\n2024-01-01 10:00:00.123\n2024-01-01\n2024-01-01T10:00:00\n",
+    )
+    .unwrap();
+
+    let query = schema_query("tests/test_schema_datetime.csv", &[("seen", ColumnType::Datetime)]);
+    let df = execute_query(query).expect("should succeed");
+
+    let seen = df.column("seen").unwrap();
+    assert_eq!(seen.null_count(), 0);
+    let values: Vec<String> = (0..4).map(|i| seen.str_value(i).unwrap().to_string()).collect();
+    assert_eq!(values, [
+        "2024-01-01 10:00:00",
+        "2024-01-01 10:00:00.123",
+        "2024-01-01 00:00:00",
+        "2024-01-01 10:00:00",
+    ]);
+}
+
+#[test]
+fn fails_when_data_does_not_match_schema() {
+    std::fs::write("tests/test_schema_bad.csv", "id,age\n1,30\n2,unknown\n").unwrap();
+
+    let query = schema_query("tests/test_schema_bad.csv", &[("age", ColumnType::Int)]);
+    let err = execute_query(query).unwrap_err().to_string();
+
+    assert!(err.contains("'age'"), "{}", err);
+    assert!(err.contains("row 2"), "{}", err);
+}
+
+#[test]
+fn pushes_filter_and_projection_into_scan() {
+    create_test_csv("tests/test_lazy.csv");
+
+    let mut query = build_query();
+    query.source = "tests/test_lazy.csv".into();
+    query.cap = Some(1);
+    let plan = build_lazy(query.clone())
+        .unwrap()
+        .describe_optimized_plan()
+        .unwrap();
+
+    assert!(plan.contains("PROJECT 2/3 COLUMNS"), "{}", plan);
+    assert!(plan.contains("SELECTION"), "{}", plan);
+
+    let df = execute_query(query).expect("should succeed");
+    assert_eq!(df.shape(), (1, 2));
+    assert_eq!(df.column("name").unwrap().str_value(0).unwrap(), "Carol");
+}
+
+#[test]
+fn declared_schema_keeps_filter_in_scan() {
+    create_test_csv("tests/test_lazy_schema.csv");
+
+    let mut query = schema_query("tests/test_lazy_schema.csv", &[("age", ColumnType::Int)]);
+    query.filter = Some(Expr::Eq("city".to_string(), Value::String("Olinda".to_string())));
+    let plan = build_lazy(query.clone())
+        .unwrap()
+        .describe_optimized_plan()
+        .unwrap();
+
+    assert!(plan.contains("SELECTION"), "{}", plan);
+
+    let df = execute_query(query).expect("should succeed");
+    assert_eq!(df.column("age").unwrap().dtype(), &polars::prelude::DataType::Int64);
+    assert_eq!(df.column("name").unwrap().str_value(0).unwrap(), "Bob");
+}
+
+#[test]
+fn streams_query_into_output_file() {
+    create_test_csv("tests/test_stream.csv");
+
+    let mut query = build_query();
+    query.source = "tests/test_stream.csv".into();
+    let streamed = execute_query_streaming(query.clone()).expect("should succeed");
+    assert!(streamed.equals(&execute_query(query.clone()).unwrap()));
+
+    let out = std::path::Path::new("tests/test_stream_out.csv");
+    sink_query(query, out, &Sink::new(FileFormat::Csv)).expect("should succeed");
+
+    let written = std::fs::read_to_string(out).unwrap();
+    assert_eq!(written, "name,age\nCarol,40\nAlice,30\n");
+}
+
+#[test]
+fn sinks_unstreamable_plans_and_returns_other_errors() {
+    std::fs::write("tests/test_sink_source.json", r#"[{"name": "Ana", "age": 30}, {"name": "Bia", "age": 22}]"#).unwrap();
+    let query = Query { source: "tests/test_sink_source.json".into(), ..Default::default() };
+    let out = std::path::Path::new("tests/test_sink_json_out.csv");
+    sink_query(query, out, &Sink::new(FileFormat::Csv)).expect("eager sources fall back to collecting");
+    assert_eq!(std::fs::read_to_string(out).unwrap(), "name,age\nAna,30\nBia,22\n");
+
+    std::fs::write("tests/test_sink_bad.csv", "name,age\nAna,30\nBia,old\n").unwrap();
+    let query = schema_query("tests/test_sink_bad.csv", &[("age", ColumnType::Int)]);
+    let out = std::path::Path::new("tests/test_sink_bad_out.csv");
+    let err = sink_query(query, out, &Sink::new(FileFormat::Csv)).unwrap_err().to_string();
+    assert!(err.contains("row 2"), "{}", err);
+}
+
+#[test]
+fn executes_join_and_aggregate_plans() {
+    create_sales_csv("tests/test_plan_sales.csv");
+    create_test_csv("tests/test_plan_people.csv");
+
+    let joined = LogicalPlan::Join {
+        left: Box::new(LogicalPlan::Scan { source: "tests/test_plan_sales.csv".into() }),
+        right: Box::new(LogicalPlan::Scan { source: "tests/test_plan_people.csv".into() }),
+        on: vec!["city".to_string()],
+        kind: JoinKind::Left,
+    };
+    let plan = LogicalPlan::Aggregate {
+        input: Box::new(joined),
+        keys: vec!["city".to_string()],
+        aggregations: vec![Aggregation {
+            func: AggFunc::Count,
+            column: "name".to_string(),
+            alias: "people".to_string(),
+        }],
+    };
+    let df = execute_plan(plan).expect("should succeed");
+
+    // Recife has 3 sales rows x 2 people; Olinda's single row matches Bob.
+    assert_eq!(df.get_column_names(), vec!["city", "people"]);
+    assert_eq!(df.column("city").unwrap().str_value(0).unwrap(), "Recife");
+    assert_eq!(df.column("people").unwrap().get(0).unwrap().to_string(), "6");
+    assert_eq!(df.column("people").unwrap().get(1).unwrap().to_string(), "1");
+}
+
+#[test]
+fn explains_plans_and_pushdowns() {
+    create_test_csv("tests/test_explain.csv");
+
+    let mut query = build_query();
+    query.source = "tests/test_explain.csv".into();
+    query.cap = Some(1);
+    let text = explain(query).expect("should explain");
+
+    assert!(text.contains("== Logical plan ==\nLimit 1\n  Sort by age desc\n    Project name, age\n      Filter age > 25\n"), "{}", text);
+    assert!(text.contains("== Physical plan (Polars) =="), "{}", text);
+    assert!(text.contains("tests/test_explain.csv: reads 2/3 columns (projection pushed down), filter pushed down"), "{}", text);
+}
+
+#[test]
+fn explains_sources_read_whole_when_run() {
+    std::fs::write("tests/test_explain.json", r#"[{"name": "Ana", "age": 30}, {"name": "Bia", "age": 22}]"#).unwrap();
+
+    let mut query = build_query();
+    query.source = "tests/test_explain.json".into();
+    let text = explain(query).expect("should explain");
+
+    assert!(text.contains("tests/test_explain.json: read whole when the query runs (no pushdown)"), "{}", text);
+}
+
+#[test]
+fn rejects_datetimes_outside_the_nanosecond_range() {
+    use polars::prelude::{DataFrame, DataType, NamedFrom, ParquetWriter, Series, TimeUnit};
+
+    let seen = Series::new("seen", [0i64]).cast(&DataType::Datetime(TimeUnit::Nanoseconds, None)).unwrap();
+    let mut frame = DataFrame::new(vec![seen]).unwrap();
+    ParquetWriter::new(File::create("tests/test_coerce_ns.parquet").unwrap())
+        .finish(&mut frame)
+        .unwrap();
+    let query = |value: &str| Query {
+        source: "tests/test_coerce_ns.parquet".into(),
+        filter: Some(Expr::Lt("seen".to_string(), Value::String(value.to_string()))),
+        ..Default::default()
+    };
+
+    assert_eq!(execute_query(query("2262-01-01")).expect("should succeed").height(), 1);
+    let err = execute_query(query("2300-01-01")).unwrap_err().to_string();
+    assert!(err.contains("'seen'") && err.contains("\"2300-01-01\" is outside the range"), "{}", err);
+}
+
+#[test]
+fn coerces_literals_to_column_types() {
+    std::fs::write(
+        "tests/test_coerce.csv",
+        "name,age,score,joined,active\nAna,30,7.5,2024-01-10,yes\nBia,22,4.0,2023-12-31,no\n",
+    )
+    .unwrap();
+    let mut query = schema_query("tests/test_coerce.csv", &[("joined", ColumnType::Date), ("active", ColumnType::Bool)]);
+    let filtered = |filter: Expr| {
+        let mut query = query.clone();
+        query.filter = Some(filter);
+        execute_query(query).expect("should succeed").height()
+    };
+
+    assert_eq!(filtered(Expr::Gt("score".to_string(), Value::Number(5))), 1);
+    assert_eq!(filtered(Expr::Gt("age".to_string(), Value::Float(25.5))), 1);
+    assert_eq!(filtered(Expr::Eq("age".to_string(), Value::String("22".to_string()))), 1);
+    assert_eq!(filtered(Expr::Gte("joined".to_string(), Value::String("2024-01-01".to_string()))), 1);
+    assert_eq!(filtered(Expr::Eq("active".to_string(), Value::String("no".to_string()))), 1);
+
+    query.filter = Some(Expr::Gt("age".to_string(), Value::String("thirty".to_string())));
+    let err = execute_query(query).unwrap_err().to_string();
+    assert!(err.contains("\"thirty\" is not a number"), "{}", err);
+}
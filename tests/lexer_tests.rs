@@ -48,3 +48,40 @@ fn parses_operators_correctly() {
 
     assert_eq!(tokens, expected);
 }
+
+#[test]
+fn records_token_spans() {
+    let tokens = query_compiler::lexer::tokenize_spanned("show  age");
+
+    assert_eq!(tokens[1].0, Token::Ident("age".into()));
+    assert_eq!((tokens[1].1.start, tokens[1].1.end), (6, 9));
+}
+
+#[test]
+fn keyword_list_matches_tokens() {
+    for keyword in query_compiler::lexer::KEYWORDS {
+        let tokens = tokenize(keyword);
+        assert!(!matches!(tokens[0], Token::Ident(_)), "{} is not a keyword token", keyword);
+    }
+
+    // And the other way: every word token the lexer declares is listed.
+    let source = include_str!("../src/lexer/mod.rs");
+    let words: Vec<&str> = source
+        .split("#[token(\"")
+        .skip(1)
+        .filter_map(|rest| rest.split('"').next())
+        .filter(|spelling| spelling.chars().all(|c| c.is_ascii_alphabetic() || c == '_'))
+        .collect();
+    assert_eq!(words.len(), query_compiler::lexer::KEYWORDS.len());
+    for word in words {
+        assert!(query_compiler::lexer::KEYWORDS.contains(&word), "keyword token {} is missing from KEYWORDS", word);
+    }
+}
+
+#[test]
+fn keeps_comments_on_request() {
+    let tokens = query_compiler::lexer::tokenize_with_comments("show name # who \"quoted\"\r\ncap 1").unwrap();
+
+    assert_eq!(tokens[2].0, Token::Comment("# who \"quoted\"".into()));
+    assert_eq!(tokens[3].0, Token::Cap);
+}
@@ -1,5 +1,5 @@
 use query_compiler::parser::Parser;
-use query_compiler::ast::{Expr, SortDirection, Query};
+use query_compiler::ast::{Expr, SortDirection, Query, CsvOptions, Encoding, FileFormat, ColumnType, Sample, SampleSize, Cluster, Pivot, Unpivot, AggFunc, WriteTo, WriteMode};
 use query_compiler::lexer::tokenize;
 
 /// Helper to parse valid queries and panic if parsing fails.
@@ -18,7 +18,7 @@ fn parses_basic_query() {
         sort by age desc
     "#);
 
-    assert_eq!(query.source, "data.csv");
+    assert_eq!(query.source.paths, vec!["data.csv"]);
     assert_eq!(query.show, vec!["name", "age"]);
     assert!(matches!(query.filter.as_ref().unwrap(), Expr::Gt(_, _)));
     assert_eq!(query.sort.as_ref().unwrap().column, "age");
@@ -69,3 +69,205 @@ fn fails_when_missing_source_clause() {
     let mut parser = Parser::new(tokens);
     assert!(parser.parse_query().is_err());
 }
+
+#[test]
+fn parses_sample_clauses() {
+    let rows = parse_ok(r#"source "data.csv" sample 1000 rows seed 42"#);
+    assert_eq!(rows.sample, Some(Sample { size: SampleSize::Rows(1000), seed: Some(42) }));
+
+    let percent = parse_ok(r#"source "data.csv" sample 5%"#);
+    assert_eq!(percent.sample, Some(Sample { size: SampleSize::Percent(5.0), seed: None }));
+}
+
+#[test]
+fn fails_on_sample_percentage_above_100() {
+    let tokens = tokenize(r#"source "data.csv" sample 150%"#);
+    let mut parser = Parser::new(tokens);
+    assert!(parser.parse_query().is_err());
+}
+
+#[test]
+fn parses_cluster_clause() {
+    let query = parse_ok(r#"source "data.csv" cluster by age, price into 4 as segment seed 7 with centroids"#);
+
+    assert_eq!(query.cluster, Some(Cluster {
+        columns: vec!["age".into(), "price".into()],
+        k: 4,
+        alias: "segment".into(),
+        seed: Some(7),
+        centroids: true,
+    }));
+}
+
+#[test]
+fn parses_pivot_and_unpivot_clauses() {
+    let query = parse_ok(r#"
+        source "sales.csv"
+        pivot sum(amount) by month for city
+        unpivot jan, feb, mar into month, value
+    "#);
+
+    assert_eq!(query.pivot, Some(Pivot {
+        agg: AggFunc::Sum,
+        values: "amount".into(),
+        columns: "month".into(),
+        index: vec!["city".into()],
+    }));
+    assert_eq!(query.unpivot, Some(Unpivot {
+        columns: vec!["jan".into(), "feb".into(), "mar".into()],
+        variable: "month".into(),
+        value: "value".into(),
+    }));
+}
+
+#[test]
+fn parses_multiple_sources_with_file_tag() {
+    let query = parse_ok(r#"source "jan.csv", "sales/*.csv" with source_file show name"#);
+
+    assert_eq!(query.source.paths, vec!["jan.csv", "sales/*.csv"]);
+    assert!(query.source.tag_file);
+    assert_eq!(query.show, vec!["name"]);
+}
+
+#[test]
+fn parses_source_dialect_options() {
+    let query = parse_ok(r##"
+        source "x.csv" with (delimiter ";", header false, null "NA", encoding "latin1", skip_rows 2, comment "#", source_file)
+        show a
+    "##);
+
+    assert_eq!(query.source.options, CsvOptions {
+        delimiter: b';',
+        header: false,
+        null_values: vec!["NA".into()],
+        encoding: Encoding::Latin1,
+        skip_rows: 2,
+        comment: Some("#".into()),
+        ..Default::default()
+    });
+    assert!(query.source.tag_file);
+    assert_eq!(query.show, vec!["a"]);
+}
+
+#[test]
+fn fails_on_unknown_source_option() {
+    let tokens = tokenize(r#"source "x.csv" with (separator ";")"#);
+    let mut parser = Parser::new(tokens);
+    assert!(parser.parse_query().is_err());
+}
+
+#[test]
+fn parses_explicit_source_format() {
+    let query = parse_ok(r#"source "exports/latest" as parquet show id"#);
+
+    assert_eq!(query.source.format, Some(FileFormat::Parquet));
+    assert_eq!(query.show, vec!["id"]);
+}
+
+#[test]
+fn parses_source_schema_declaration() {
+    let query = parse_ok(r#"source "x.csv" with (delimiter ";") schema exact (id string, age int, joined date)"#);
+    let schema = query.source.schema.unwrap();
+
+    assert!(schema.exact);
+    let types: Vec<_> = schema.columns.iter().map(|c| (c.name.as_str(), c.ty)).collect();
+    assert_eq!(types, vec![("id", ColumnType::String), ("age", ColumnType::Int), ("joined", ColumnType::Date)]);
+    assert_eq!(query.source.options.delimiter, b';');
+}
+
+#[test]
+fn parses_explain_prefix() {
+    let query = parse_ok(r#"explain source "data.csv" filter age > 20"#);
+
+    assert!(query.explain);
+    assert!(!parse_ok(r#"source "data.csv""#).explain);
+}
+
+#[test]
+fn suggests_keyword_for_misspelled_clause() {
+    let mut parser = Parser::new(tokenize(r#"source "data.csv" filtr age > 3"#));
+    let err = parser.parse_query().unwrap_err().to_string();
+
+    assert!(err.contains("Unknown clause 'filtr'; did you mean 'filter'?"), "{}", err);
+}
+
+#[test]
+fn rejects_tokens_after_the_query() {
+    for (input, expected) in [
+        (r#"source "data.csv" cap 3 extra"#, "Unknown clause 'extra'"),
+        (r#"source "data.csv" filter age > 3)"#, "Unexpected token RParen after query"),
+        (r#"source "data.csv" show name 42"#, "Unexpected token Number(42) after query"),
+    ] {
+        let err = Parser::new(tokenize(input)).parse_query().unwrap_err().to_string();
+        assert!(err.contains(expected), "{}: {}", input, err);
+    }
+}
+
+#[test]
+fn suggests_source_option() {
+    let mut parser = Parser::new(tokenize(r#"source "data.csv" with delimeter ";""#));
+    let err = parser.parse_query().unwrap_err().to_string();
+
+    assert!(err.contains("did you mean 'delimiter'?"), "{}", err);
+}
+
+#[test]
+fn parses_write_clause_with_partitions() {
+    let query = parse_ok(r#"source "sales.csv" filter amount > 0 write to "out/" partition by year, city"#);
+    assert_eq!(
+        query.write,
+        Some(WriteTo { path: "out/".into(), partition_by: vec!["year".into(), "city".into()], ..Default::default() })
+    );
+
+    let query = parse_ok(r#"source "sales.csv" write to "result.csv""#);
+    assert_eq!(query.write, Some(WriteTo { path: "result.csv".into(), ..Default::default() }));
+
+    let mut parser = Parser::new(tokenize(r#"source "sales.csv" write "out/""#));
+    assert!(parser.parse_query().is_err());
+}
+
+#[test]
+fn parses_write_format_and_mode() {
+    let query = parse_ok(r#"source "sales.csv" write to "log" format ndjson mode append"#);
+    assert_eq!(
+        query.write,
+        Some(WriteTo {
+            path: "log".into(),
+            format: Some(FileFormat::Ndjson),
+            mode: WriteMode::Append,
+            partition_by: vec![],
+        })
+    );
+
+    let query = parse_ok(r#"source "sales.csv" write to "out/" mode error_if_exists partition by city format parquet"#);
+    let write = query.write.unwrap();
+    assert_eq!((write.format, write.mode), (Some(FileFormat::Parquet), WriteMode::ErrorIfExists));
+
+    let err = Parser::new(tokenize(r#"source "sales.csv" write to "x" mode replace"#)).parse_query().unwrap_err();
+    assert!(err.to_string().contains("Unknown write mode 'replace'"));
+}
+
+#[test]
+fn parses_stdin_sources() {
+    assert_eq!(parse_ok("source stdin").source.paths, vec!["-".to_string()]);
+    assert_eq!(parse_ok(r#"source "-" as ndjson"#).source.paths, vec!["-".to_string()]);
+}
+
+#[test]
+fn parses_backticked_columns_that_clash_with_keywords() {
+    let query = parse_ok(r#"source "data.csv" filter `sample` > 3 show `first name`, `seed` sort by `write` desc"#);
+
+    assert_eq!(query.show, vec!["first name", "seed"]);
+    assert_eq!(query.filter, Some(Expr::Gt("sample".into(), query_compiler::ast::Value::Number(3))));
+    assert_eq!(query.sort.unwrap().column, "write");
+}
+
+#[test]
+fn keeps_column_spans_out_of_the_query() {
+    let input = r#"source "data.csv" filter age > 3 show name"#;
+    let mut parser = Parser::with_spans(query_compiler::lexer::tokenize_spanned(input));
+    let query = parser.parse_query().expect("should parse");
+
+    assert_eq!(query, parse_ok(input));
+    assert_eq!(&input[parser.column_spans().span_of("name").unwrap().start..], "name");
+}
@@ -0,0 +1,164 @@
+use query_compiler::plan::{lower, optimize, to_dot, to_mermaid, CompareOp, GraphNode, LogicalPlan, PlanExpr};
+use query_compiler::ast::{Expr, Query, Sort, SortDirection, Value};
+
+fn scan() -> LogicalPlan {
+    LogicalPlan::Scan { source: "data.csv".into() }
+}
+
+fn filter(input: LogicalPlan, predicate: PlanExpr) -> LogicalPlan {
+    LogicalPlan::Filter { input: Box::new(input), predicate }
+}
+
+fn sort(input: LogicalPlan, column: &str) -> LogicalPlan {
+    LogicalPlan::Sort {
+        input: Box::new(input),
+        sort: Sort { column: column.to_string(), direction: SortDirection::Asc },
+    }
+}
+
+fn gt(column: &str, n: i64) -> PlanExpr {
+    PlanExpr::compare(CompareOp::Gt, column, Value::Number(n))
+}
+
+#[test]
+fn lowers_clauses_in_execution_order() {
+    let query = Query {
+        source: "data.csv".into(),
+        filter: Some(Expr::Gt("age".to_string(), Value::Number(25))),
+        show: vec!["name".to_string()],
+        cap: Some(5),
+        unique: true,
+        ..Default::default()
+    };
+
+    let plan = lower(query).unwrap();
+
+    let expected = LogicalPlan::Limit {
+        input: Box::new(LogicalPlan::Distinct {
+            input: Box::new(LogicalPlan::Project {
+                input: Box::new(filter(scan(), gt("age", 25))),
+                columns: vec!["name".to_string()],
+            }),
+        }),
+        n: 5,
+    };
+    assert_eq!(plan, expected);
+}
+
+#[test]
+fn folds_constants_and_simplifies_predicates() {
+    let always = PlanExpr::Compare(
+        CompareOp::Lt,
+        Box::new(PlanExpr::Literal(Value::Number(1))),
+        Box::new(PlanExpr::Literal(Value::Float(2.5))),
+    );
+    let plan = filter(scan(), always.and(gt("age", 18).or(gt("age", 18))));
+
+    assert_eq!(optimize(plan), filter(scan(), gt("age", 18)));
+
+    let never = PlanExpr::Literal(Value::Bool(false)).and(gt("age", 18));
+    assert_eq!(optimize(filter(scan(), never.clone())), filter(scan(), PlanExpr::Literal(Value::Bool(false))));
+}
+
+#[test]
+fn keeps_tighter_bound_on_same_column() {
+    let plan = filter(scan(), gt("age", 18).and(gt("age", 21)));
+    assert_eq!(optimize(plan), filter(scan(), gt("age", 21)));
+
+    let plan = filter(scan(), gt("age", 18).or(gt("age", 21)));
+    assert_eq!(optimize(plan), filter(scan(), gt("age", 18)));
+
+    let untouched = filter(scan(), gt("age", 18).and(gt("price", 21)));
+    assert_eq!(optimize(untouched.clone()), untouched);
+}
+
+#[test]
+fn merges_adjacent_filters() {
+    let plan = filter(filter(scan(), gt("age", 18)), gt("price", 10));
+
+    assert_eq!(optimize(plan), filter(scan(), gt("age", 18).and(gt("price", 10))));
+}
+
+#[test]
+fn removes_sort_hidden_by_same_sort() {
+    let plan = sort(filter(sort(scan(), "age"), gt("price", 10)), "age");
+    assert_eq!(optimize(plan), sort(filter(scan(), gt("price", 10)), "age"));
+
+    // A sort on another column decides the order of ties, so it stays.
+    let plan = sort(sort(scan(), "name"), "age");
+    assert_eq!(optimize(plan.clone()), plan);
+}
+
+#[test]
+fn pushes_limit_below_projection_and_merges_limits() {
+    let plan = LogicalPlan::Limit {
+        input: Box::new(LogicalPlan::Limit {
+            input: Box::new(LogicalPlan::Project {
+                input: Box::new(scan()),
+                columns: vec!["name".to_string()],
+            }),
+            n: 3,
+        }),
+        n: 10,
+    };
+
+    let expected = LogicalPlan::Project {
+        input: Box::new(LogicalPlan::Limit { input: Box::new(scan()), n: 3 }),
+        columns: vec!["name".to_string()],
+    };
+    assert_eq!(optimize(plan), expected);
+
+    // `sort` + `cap` stays together so Polars can run it as a top-k.
+    let top_k = LogicalPlan::Limit { input: Box::new(sort(scan(), "age")), n: 3 };
+    assert_eq!(optimize(top_k.clone()), top_k);
+}
+
+#[test]
+fn displays_plan_as_indented_tree() {
+    let predicate = gt("age", 18).and(PlanExpr::compare(CompareOp::Eq, "city", Value::String("Recife".into())).or(gt("price", 10)));
+    let plan = LogicalPlan::Limit { input: Box::new(sort(filter(scan(), predicate), "age")), n: 3 };
+
+    assert_eq!(
+        plan.to_string(),
+        "Limit 3\n  Sort by age asc\n    Filter age > 18 and (city == \"Recife\" or price > 10)\n      Scan \"data.csv\"\n"
+    );
+}
+
+#[test]
+fn renders_plan_as_dot_and_mermaid() {
+    let plan = LogicalPlan::Limit { input: Box::new(filter(scan(), gt("age", 18))), n: 3 };
+    let graph = GraphNode::from(&plan);
+
+    assert_eq!(
+        to_dot(&graph),
+        "digraph plan {\n    node [shape=box, fontname=\"monospace\"];\n    \
+         n0 [label=\"Limit 3\"];\n    n1 [label=\"Filter age > 18\"];\n    \
+         n2 [label=\"Scan \\\"data.csv\\\"\"];\n    n1 -> n2;\n    n0 -> n1;\n}\n"
+    );
+    assert_eq!(
+        to_mermaid(&graph),
+        "flowchart TD\n    n0[\"Limit 3\"]\n    n1[\"Filter age > 18\"]\n    \
+         n2[\"Scan #quot;data.csv#quot;\"]\n    n1 --> n2\n    n0 --> n1\n"
+    );
+}
+
+#[test]
+fn builds_graph_from_query_clauses() {
+    let query = Query {
+        source: "data.csv".into(),
+        filter: Some(Expr::And(
+            Box::new(Expr::Gt("age".to_string(), Value::Number(25))),
+            Box::new(Expr::Eq("city".to_string(), Value::String("Recife".to_string()))),
+        )),
+        cap: Some(10),
+        ..Default::default()
+    };
+    let graph = GraphNode::from(&query);
+
+    let labels: Vec<&str> = graph.children.iter().map(|c| c.label.as_str()).collect();
+    assert_eq!(labels, vec!["Scan \"data.csv\"", "Filter", "Cap 10"]);
+
+    let condition = &graph.children[1].children[0];
+    assert_eq!(condition.label, "and");
+    assert_eq!(condition.children[1].label, "city == \"Recife\"");
+}
@@ -0,0 +1,88 @@
+use query_compiler::ast::Query;
+use query_compiler::lexer::tokenize;
+use query_compiler::parser::Parser;
+use query_compiler::printer::{format_query, print_query};
+
+fn parse(input: &str) -> Query {
+    Parser::new(tokenize(input)).parse_query().expect("should parse")
+}
+
+/// Parsing the printed query gives back the same query, and printing is stable.
+fn assert_round_trip(input: &str) -> String {
+    let query = parse(input);
+    let printed = print_query(&query);
+    assert_eq!(parse(&printed), query, "{}", printed);
+    assert_eq!(print_query(&parse(&printed)), printed);
+    printed
+}
+
+#[test]
+fn prints_one_clause_per_line_in_running_order() {
+    let printed = assert_round_trip(
+        r#"source "people.csv" |> cap 10 sort by age DESC show name age filter age > 30 AND age < 60 unique"#,
+    );
+
+    assert_eq!(printed, "source \"people.csv\"\nfilter age > 30 and age < 60\nshow name, age\nunique\nsort by age desc\ncap 10\n");
+}
+
+#[test]
+fn prints_only_the_parentheses_the_grammar_needs() {
+    let printed = assert_round_trip(r#"source "a.csv" filter ((a == 1) and (b == 2)) or (c == 3 and (d == 4 or e == 5))"#);
+    assert_eq!(printed, "source \"a.csv\"\nfilter a == 1 and b == 2 or c == 3 and (d == 4 or e == 5)\n");
+
+    let printed = assert_round_trip(r#"source "a.csv" filter a == 1 and (b == 2 and c == 3) or (d == 4 or e == 5)"#);
+    assert_eq!(printed, "source \"a.csv\"\nfilter a == 1 and (b == 2 and c == 3) or (d == 4 or e == 5)\n");
+
+    let printed = assert_round_trip(r#"source "a.csv" map flag = (price >= 10.0 or active == true)"#);
+    assert_eq!(printed, "source \"a.csv\"\nmap flag = (price >= 10.0 or active == true)\n");
+}
+
+#[test]
+fn round_trips_every_clause() {
+    assert_round_trip(
+        r##"explain source "jan.csv", stdin as csv
+            with (delimiter "\t", quote "", header false, null "NA", null "-", encoding "latin1", skip_rows 2, comment "#", source_file)
+            schema exact (id string, "first name" string, age int, score float, ok bool, joined date, seen datetime)
+        filter city != "Recife" and score <= 2.5 or active == false
+        sample 12.5% seed 3
+        cluster by age, score into 4 as segment seed 7 with centroids
+        pivot avg(amount) by month for city, year
+        unpivot jan, feb into month, value
+        show name
+        map adult = age >= 18
+        unique
+        sort by name asc
+        cap 0
+        write to "out/" format parquet mode error_if_exists partition by year, city"##,
+    );
+    assert_round_trip(r#"source "a.csv" with source_file sample 100 rows write to "b.csv" mode append"#);
+    assert_round_trip(r#"source "-" filter ratio > 3.0"#);
+
+    let printed = assert_round_trip(r#"source "a.csv" filter `sample` == 1 show `first name`, `as`"#);
+    assert_eq!(printed, "source \"a.csv\"\nfilter `sample` == 1\nshow `first name`, `as`\n");
+}
+
+#[test]
+fn keeps_comments_next_to_their_clauses() {
+    let input = "# Adults in Recife\nsource \"people.csv\"  # exported nightly\nshow name\n# only adults\nfilter age >= 18\n# the end\n";
+
+    let formatted = format_query(input).unwrap();
+
+    assert_eq!(
+        formatted,
+        "# Adults in Recife\nsource \"people.csv\"  # exported nightly\n# only adults\nfilter age >= 18\nshow name\n# the end\n"
+    );
+    assert_eq!(format_query(&formatted).unwrap(), formatted);
+    assert_eq!(parse(&formatted), parse(input));
+}
+
+#[test]
+fn refuses_to_format_text_the_lexer_drops() {
+    let input = "source \"a.csv\"\nfilter a == -3\n";
+    let err = format_query(input).unwrap_err();
+
+    assert_eq!(
+        err.render(input),
+        "Syntax error at line 2, column 13: Unrecognised text '-'\n  filter a == -3\n              ^"
+    );
+}
@@ -0,0 +1,105 @@
+use query_compiler::repl::{common_prefix, complete, Session};
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn columns() -> Vec<String> {
+    vec!["name".to_string(), "age".to_string(), "nation".to_string()]
+}
+
+#[test]
+fn completes_keywords_and_columns() {
+    let (start, candidates) = complete("filter na", &columns());
+    assert_eq!(start, 7);
+    assert_eq!(candidates, vec!["name", "nation"]);
+
+    let (start, candidates) = complete("SOU", &[]);
+    assert_eq!(start, 0);
+    assert_eq!(candidates, vec!["source"]);
+}
+
+#[test]
+fn completes_meta_commands_but_not_inside_strings() {
+    assert_eq!(complete(".ex", &[]).1, vec![".exit"]);
+    assert_eq!(complete(".s", &[]).1, vec![".schema"]);
+    assert!(complete("source \"na", &columns()).1.is_empty());
+    assert_eq!(common_prefix(&["name".to_string(), "nation".to_string()]), "na");
+}
+
+#[test]
+fn runs_queries_and_meta_commands() {
+    let csv = "tests/test_repl_session.csv";
+    fs::write(csv, "name,age\nAlice,30\nBob,22\n").unwrap();
+    let mut session = Session::new();
+
+    let out = session.eval(&format!("source \"{}\"\nfilter age > 25\n", csv)).unwrap();
+    assert!(out.contains("Alice") && !out.contains("Bob"), "{}", out);
+    assert_eq!(session.columns(), ["name", "age"]);
+
+    let schema = session.eval(".schema").unwrap();
+    assert!(schema.contains("name") && schema.contains("i64"), "{}", schema);
+
+    assert_eq!(session.eval(".tokens").unwrap(), "Printing tokens on\n");
+    let out = session.eval(&format!("source \"{}\"", csv)).unwrap();
+    assert!(out.starts_with("# Tokens:\nSource\n"), "{}", out);
+
+    assert!(session.eval(".nope").is_err());
+}
+
+#[test]
+fn keeps_sources_loaded_until_reload() {
+    let csv = "tests/test_repl_reload.csv";
+    fs::write(csv, "name,age\nAlice,30\n").unwrap();
+    let mut session = Session::new();
+    let query = format!("source \"{}\"", csv);
+    assert!(session.eval(&query).unwrap().contains("Alice"));
+
+    fs::write(csv, "name,age\nCarol,41\n").unwrap();
+    assert!(session.eval(&query).unwrap().contains("Alice"));
+
+    session.eval(".reload").unwrap();
+    assert!(session.eval(&query).unwrap().contains("Carol"));
+}
+
+#[test]
+fn rejects_stdin_sources() {
+    let mut session = Session::new();
+    for query in ["source stdin", r#"source "-""#] {
+        let err = session.eval(query).unwrap_err().to_string();
+        assert!(err.contains("can't be a source"), "{}", err);
+    }
+}
+
+#[test]
+fn reads_multi_line_queries_from_piped_input() {
+    let csv = "tests/test_repl_piped.csv";
+    fs::write(csv, "name,age\nAlice,30\nBob,22\n").unwrap();
+    let home = "tests/test_repl_home";
+    fs::create_dir_all(home).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_query_compiler"))
+        .arg("repl")
+        .env("HOME", home)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let input = format!("source \"{}\"\n# the young ones\nfilter age < 25\n\n.quit\n", csv);
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(stdout.contains("Bob") && !stdout.contains("Alice"), "{}", stdout);
+    let history = fs::read_to_string(format!("{}/.csv_query_history", home)).unwrap();
+    assert!(history.ends_with(&format!("source \"{}\"\n# the young ones\nfilter age < 25\n.quit\n", csv)), "{}", history);
+}
+
+#[test]
+fn prints_tokens_of_a_query_that_does_not_parse() {
+    let mut session = Session::new();
+    session.eval(".tokens").unwrap();
+    let err = session.eval("source \"a.csv\" cap").unwrap_err().to_string();
+    assert!(err.starts_with("# Tokens:\nSource\n") && err.contains("Cap"), "{}", err);
+    assert!(err.contains("Syntax error"), "{}", err);
+}
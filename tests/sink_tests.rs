@@ -0,0 +1,271 @@
+use query_compiler::ast::{FileFormat, WriteMode};
+use query_compiler::sink::{Compression, CsvSinkOptions, QuoteStyle, Sink, Table, TableFormat};
+use polars::prelude::*;
+use std::fs::File;
+use std::path::Path;
+
+fn people() -> DataFrame {
+    df!(
+        "name" => ["Alice", "Bob, Jr."],
+        "age" => [30i64, 22],
+    )
+    .unwrap()
+}
+
+fn write(sink: &Sink) -> String {
+    let mut out = Vec::new();
+    sink.write(&mut people(), &mut out).expect("should succeed");
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn writes_csv_with_default_dialect() {
+    assert_eq!(write(&Sink::new(FileFormat::Csv)), "name,age\nAlice,30\n\"Bob, Jr.\",22\n");
+}
+
+#[test]
+fn writes_excel_csv_with_bom_and_semicolons() {
+    let sink = Sink::new(FileFormat::Csv).with_csv(CsvSinkOptions::excel());
+
+    assert_eq!(write(&sink), "\u{feff}name;age\nAlice;30\nBob, Jr.;22\n");
+}
+
+#[test]
+fn quotes_csv_fields_by_style() {
+    let csv = CsvSinkOptions { quote_style: QuoteStyle::NonNumeric, header: false, ..Default::default() };
+    let sink = Sink::new(FileFormat::Csv).with_csv(csv);
+
+    assert_eq!(write(&sink), "\"Alice\",30\n\"Bob, Jr.\",22\n");
+    assert_eq!(QuoteStyle::from_name("non_numeric"), Some(QuoteStyle::NonNumeric));
+}
+
+#[test]
+fn writes_json_array_and_ndjson() {
+    // Keys keep the column order, which here isn't alphabetical.
+    assert_eq!(
+        write(&Sink::new(FileFormat::Json)),
+        "[\n  {\n    \"name\": \"Alice\",\n    \"age\": 30\n  },\n  {\n    \"name\": \"Bob, Jr.\",\n    \"age\": 22\n  }\n]\n"
+    );
+
+    assert_eq!(
+        write(&Sink::new(FileFormat::Ndjson)),
+        "{\"name\":\"Alice\",\"age\":30}\n{\"name\":\"Bob, Jr.\",\"age\":22}\n"
+    );
+}
+
+#[test]
+fn round_trips_compressed_parquet_and_ipc() {
+    let parquet = Path::new("tests/test_sink_out/people.parquet");
+    let sink = Sink::new(FileFormat::Parquet).with_compression(Compression::Snappy);
+    sink.write_file(&mut people(), parquet).expect("should succeed");
+    let read = ParquetReader::new(File::open(parquet).unwrap()).finish().unwrap();
+    assert!(read.equals(&people()));
+
+    let ipc = Path::new("tests/test_sink_out/people.arrow");
+    let sink = Sink::new(FileFormat::Ipc).with_compression(Compression::Lz4);
+    sink.write_file(&mut people(), ipc).expect("should succeed");
+    let read = IpcReader::new(File::open(ipc).unwrap()).finish().unwrap();
+    assert!(read.equals(&people()));
+}
+
+#[test]
+fn rejects_compression_the_format_lacks() {
+    let path = Path::new("tests/test_sink_gzip.arrow");
+    let _ = std::fs::remove_file(path);
+    let sink = Sink::new(FileFormat::Ipc).with_compression(Compression::Gzip);
+
+    let err = sink.write_file(&mut people(), path).unwrap_err();
+
+    assert!(err.to_string().contains("Arrow IPC supports lz4 or zstd"));
+    assert!(!path.exists());
+}
+
+#[test]
+fn writes_json_values_with_their_types() {
+    let point = StructChunked::new(
+        "point",
+        &[Series::new("x", [1i32, 3]), Series::new("y", [Some("a"), None])],
+    )
+    .unwrap()
+    .into_series();
+    let mut df = DataFrame::new(vec![
+        Series::new("small", [Some(7i16), None]),
+        Series::new("count", [4u32, 5]),
+        Series::new("ratio", [0.5f64, f64::NAN]),
+        Series::new("price", [12.34f64, -0.05]).cast(&DataType::Decimal(Some(10), Some(2))).unwrap(),
+        Series::new("day", [19723i32, 0]).cast(&DataType::Date).unwrap(),
+        Series::new("at", [1_704_164_645_500i64, 10_000_000_000_000])
+            .cast(&DataType::Datetime(TimeUnit::Milliseconds, None))
+            .unwrap(),
+        Series::new("waited", [90_000i64, 500])
+            .cast(&DataType::Duration(TimeUnit::Milliseconds))
+            .unwrap(),
+        Series::new("tags", [Series::new("", [1i64, 2]), Series::new("", Vec::<i64>::new())]),
+        point,
+    ])
+    .unwrap();
+
+    let mut out = Vec::new();
+    Sink::new(FileFormat::Json).write(&mut df, &mut out).expect("should succeed");
+    let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+
+    assert_eq!(
+        json,
+        serde_json::json!([
+            {
+                "small": 7, "count": 4, "ratio": 0.5, "price": "12.34", "day": "2024-01-01",
+                "at": "2024-01-02T03:04:05.500", "waited": "PT90S", "tags": [1, 2],
+                "point": {"x": 1, "y": "a"},
+            },
+            {
+                "small": null, "count": 5, "ratio": null, "price": "-0.05", "day": "1970-01-01",
+                "at": "2286-11-20T17:46:40", "waited": "PT0.5S", "tags": [],
+                "point": {"x": 3, "y": null},
+            },
+        ])
+    );
+}
+
+#[test]
+fn writes_json_null_for_datetimes_out_of_range() {
+    let mut df = DataFrame::new(vec![
+        Series::new("at", [0i64, 10_000_000_000_000_000])
+            .cast(&DataType::Datetime(TimeUnit::Milliseconds, None))
+            .unwrap(),
+    ])
+    .unwrap();
+
+    let mut out = Vec::new();
+    Sink::new(FileFormat::Json).write(&mut df, &mut out).expect("should succeed");
+    let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+
+    assert_eq!(json, serde_json::json!([{"at": "1970-01-01T00:00:00"}, {"at": null}]));
+}
+
+#[test]
+fn renders_aligned_text_table() {
+    let table = Table::new(TableFormat::Table);
+
+    assert_eq!(table.render(&people()), "name      age\n--------  ---\nAlice      30\nBob, Jr.   22\n");
+}
+
+#[test]
+fn renders_markdown_and_html_tables() {
+    let df = df!("name" => [Some("a|b"), None], "n" => [1i64, 2]).unwrap();
+
+    assert_eq!(
+        Table::new(TableFormat::Markdown).render(&df),
+        "| name | n |\n| --- | ---: |\n| a\\|b | 1 |\n|  | 2 |\n"
+    );
+    assert_eq!(
+        Table::new(TableFormat::Html).render(&df),
+        "<table>\n  <thead>\n    <tr><th>name</th><th align=\"right\">n</th></tr>\n  </thead>\n  <tbody>\n    \
+         <tr><td>a|b</td><td align=\"right\">1</td></tr>\n    <tr><td></td><td align=\"right\">2</td></tr>\n  \
+         </tbody>\n</table>\n"
+    );
+}
+
+#[test]
+fn truncates_rows_and_cells_unless_full() {
+    let df = df!("word" => ["short", "considerably longer", "third"]).unwrap();
+    let table = Table { max_rows: Some(2), max_width: Some(8), ..Table::new(TableFormat::Table) };
+
+    assert_eq!(table.render(&df), "word\n--------\nshort\nconside…\n\n… 1 more row\n");
+    assert_eq!(table.full().render(&df).lines().count(), 5);
+}
+
+#[test]
+fn writes_hive_style_partitions() {
+    let dir = Path::new("tests/test_sink_partitions");
+    let _ = std::fs::remove_dir_all(dir);
+    let df = df!(
+        "year" => [2024i64, 2024, 2023],
+        "city" => [Some("Recife"), Some("Natal/RN"), None],
+        "amount" => [1i64, 2, 3],
+    )
+    .unwrap();
+
+    let files = Sink::new(FileFormat::Csv)
+        .write_partitioned(&df, dir, &["year".to_string(), "city".to_string()])
+        .expect("should succeed");
+
+    assert_eq!(
+        files,
+        vec![
+            dir.join("year=2024/city=Recife/part.csv"),
+            dir.join("year=2024/city=Natal%2FRN/part.csv"),
+            dir.join("year=2023/city=__HIVE_DEFAULT_PARTITION__/part.csv"),
+        ]
+    );
+    assert_eq!(std::fs::read_to_string(&files[1]).unwrap(), "amount\n2\n");
+
+    let err = Sink::new(FileFormat::Csv).write_partitioned(&df, dir, &["month".to_string()]).unwrap_err();
+    assert!(err.to_string().contains("Unknown partition column 'month'"));
+}
+
+#[test]
+fn overwriting_partitions_removes_stale_ones() {
+    let dir = Path::new("tests/test_sink_stale_partitions");
+    let _ = std::fs::remove_dir_all(dir);
+    std::fs::create_dir_all(dir).unwrap();
+    std::fs::write(dir.join("README"), "keep me\n").unwrap();
+    let sink = Sink::new(FileFormat::Csv);
+    let columns = ["year".to_string()];
+
+    sink.write_partitioned(&df!("year" => [2023i64, 2024], "amount" => [1i64, 2]).unwrap(), dir, &columns)
+        .expect("should succeed");
+    sink.write_partitioned(&df!("year" => [2024i64], "amount" => [3i64]).unwrap(), dir, &columns)
+        .expect("should succeed");
+
+    assert!(!dir.join("year=2023").exists());
+    assert_eq!(std::fs::read_to_string(dir.join("year=2024/part.csv")).unwrap(), "amount\n3\n");
+    assert!(dir.join("README").exists());
+}
+
+#[test]
+fn appends_to_csv_and_ndjson_files() {
+    let csv = Path::new("tests/test_sink_append.csv");
+    let _ = std::fs::remove_file(csv);
+    let sink = Sink::new(FileFormat::Csv).with_mode(WriteMode::Append);
+    sink.write_file(&mut people(), csv).expect("should succeed");
+    sink.write_file(&mut people().head(Some(1)), csv).expect("should succeed");
+    assert_eq!(std::fs::read_to_string(csv).unwrap(), "name,age\nAlice,30\n\"Bob, Jr.\",22\nAlice,30\n");
+
+    let ndjson = Path::new("tests/test_sink_append.ndjson");
+    let _ = std::fs::remove_file(ndjson);
+    let sink = Sink::new(FileFormat::Ndjson).with_mode(WriteMode::Append);
+    sink.write_file(&mut people().head(Some(1)), ndjson).expect("should succeed");
+    sink.write_file(&mut people().head(Some(1)), ndjson).expect("should succeed");
+    assert_eq!(std::fs::read_to_string(ndjson).unwrap().lines().count(), 2);
+
+    let err = Sink::new(FileFormat::Parquet).with_mode(WriteMode::Append).check().unwrap_err();
+    assert!(err.to_string().contains("Only CSV and NDJSON output can be appended to"));
+}
+
+#[test]
+fn appends_csv_rows_in_the_header_order() {
+    let csv = Path::new("tests/test_sink_append_order.csv");
+    std::fs::write(csv, "age,name\n41,Carol\n").unwrap();
+    let sink = Sink::new(FileFormat::Csv).with_mode(WriteMode::Append);
+    sink.write_file(&mut people().head(Some(1)), csv).expect("should succeed");
+    assert_eq!(std::fs::read_to_string(csv).unwrap(), "age,name\n41,Carol\n30,Alice\n");
+
+    std::fs::write(csv, "name,city\nCarol,Recife\n").unwrap();
+    let err = sink.write_file(&mut people(), csv).unwrap_err().to_string();
+    assert!(err.contains("its header has columns [name, city] but the result has [name, age]"), "{}", err);
+    assert_eq!(std::fs::read_to_string(csv).unwrap(), "name,city\nCarol,Recife\n");
+}
+
+#[test]
+fn refuses_to_replace_files_in_error_if_exists_mode() {
+    let path = Path::new("tests/test_sink_exists.csv");
+    std::fs::write(path, "keep me\n").unwrap();
+
+    let err = Sink::new(FileFormat::Csv)
+        .with_mode(WriteMode::ErrorIfExists)
+        .write_file(&mut people(), path)
+        .unwrap_err();
+
+    assert!(err.to_string().contains("already exists"));
+    assert_eq!(std::fs::read_to_string(path).unwrap(), "keep me\n");
+}